@@ -0,0 +1,146 @@
+// Files and links attached to tasks, opened with the configured opener command
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::app::config::AttachmentsConfig;
+use crate::app::models::Attachment;
+
+// Used unless attachments.opener is configured
+// The empty title keeps start from taking a quoted target for the window title
+const DEFAULT_OPENER: &str = if cfg!(target_os = "macos") {
+    "open"
+} else if cfg!(windows) {
+    "start \"\""
+} else {
+    "xdg-open"
+};
+
+pub fn opener(config: &AttachmentsConfig) -> String {
+    config.opener.clone().unwrap_or(DEFAULT_OPENER.to_string())
+}
+
+fn is_url(target: &str) -> bool {
+    target.contains("://") || target.starts_with("mailto:")
+}
+
+// "<path or URL> [label]"; the label defaults to the file name or the URL
+// Paths with spaces are quoted: "\"My Documents/plan.txt\" label"
+// Paths must exist and are stored absolute, so they open from any working directory
+pub fn parse_attachment(input: &str) -> Result<Attachment, String> {
+    let input = input.trim();
+    let (target, label) = match input.strip_prefix('"') {
+        Some(quoted) => quoted
+            .split_once('"')
+            .ok_or("The quoted path needs a closing quote".to_string())?,
+        None => input.split_once(char::is_whitespace).unwrap_or((input, "")),
+    };
+    if target.is_empty() {
+        return Err("Enter a path or a URL".to_string());
+    }
+
+    let target = if is_url(target) {
+        target.to_string()
+    } else {
+        let path = match target.strip_prefix("~/") {
+            Some(rest) => Path::new(&std::env::var("HOME").unwrap_or_default()).join(rest),
+            None => Path::new(target).to_path_buf(),
+        };
+        path.canonicalize()
+            .map_err(|_| format!("{} does not exist", target))?
+            .to_string_lossy()
+            .to_string()
+    };
+    let label = match label.trim() {
+        "" => Path::new(&target)
+            .file_name()
+            .filter(|_| !is_url(&target))
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or(target.clone()),
+        label => label.to_string(),
+    };
+
+    Ok(Attachment {
+        id: None,
+        target,
+        label,
+    })
+}
+
+// The opener runs through sh with the target in the ATTACHMENT variable
+#[cfg(not(windows))]
+fn opener_command(opener: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(format!("{} \"$ATTACHMENT\"", opener));
+    command
+}
+
+// cmd does not read the \" escapes of Command::arg, so the line is passed as written
+// Not tested on Windows yet
+#[cfg(windows)]
+fn opener_command(opener: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+
+    let mut command = Command::new("cmd");
+    command.arg("/C").raw_arg(format!("{} \"%ATTACHMENT%\"", opener));
+    command
+}
+
+// Start the opener in the background; A thread waits for it, so it does not stay a zombie
+pub fn open_attachment(opener: &str, attachment: &Attachment) -> Result<(), String> {
+    if !is_url(&attachment.target) && !Path::new(&attachment.target).exists() {
+        return Err(format!("{} no longer exists", attachment.target));
+    }
+    let mut child = opener_command(opener)
+        .env("ATTACHMENT", &attachment.target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", opener, e))?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn urls_are_kept_as_entered() {
+        let attachment = parse_attachment("https://example.com/spec?page=2 Spec").unwrap();
+        assert_eq!(attachment.target, "https://example.com/spec?page=2");
+        assert_eq!(attachment.label, "Spec");
+        assert_eq!(parse_attachment(" mailto:team@example.com ").unwrap().label, "mailto:team@example.com");
+    }
+
+    #[test]
+    fn quoted_paths_may_contain_spaces() {
+        let dir = std::env::temp_dir().join("task_manager_attachment_test");
+        fs::create_dir_all(dir.join("My Documents")).unwrap();
+        let file = dir.join("My Documents").join("my file.txt");
+        fs::write(&file, "").unwrap();
+        let file = file.canonicalize().unwrap().to_string_lossy().to_string();
+
+        let attachment = parse_attachment(&format!("\"{}\"", file)).unwrap();
+        assert_eq!(attachment.target, file);
+        assert_eq!(attachment.label, "my file.txt");
+
+        let attachment = parse_attachment(&format!("\"{}\"  Meeting notes ", file)).unwrap();
+        assert_eq!(attachment.label, "Meeting notes");
+
+        assert!(parse_attachment(&file).is_err());
+        assert_eq!(
+            parse_attachment(&format!("\"{}", file)).err(),
+            Some("The quoted path needs a closing quote".to_string())
+        );
+    }
+
+    #[test]
+    fn missing_paths_are_refused() {
+        assert_eq!(parse_attachment("").err(), Some("Enter a path or a URL".to_string()));
+        assert_eq!(parse_attachment("\"\"").err(), Some("Enter a path or a URL".to_string()));
+        assert!(parse_attachment("/no/such/file label").is_err());
+    }
+}
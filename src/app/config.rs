@@ -0,0 +1,116 @@
+// User configuration loaded from a TOML file
+// Every section is optional, missing values fall back to the defaults
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::{error::Error, fs, io, path::Path};
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub keys: KeysConfig,
+    pub theme: ThemeConfig,
+    pub list: ListConfig,
+    pub reminders: RemindersConfig,
+    pub pomodoro: PomodoroConfig,
+    pub planning: PlanningConfig,
+    pub focus: FocusConfig,
+    pub attachments: AttachmentsConfig,
+}
+
+// Keybindings: a preset to start from and per-action overrides,
+// for example `delete = "x"` or `next = ["j", "down"]`
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct KeysConfig {
+    pub preset: Option<String>,
+    pub bindings: HashMap<String, KeyList>,
+}
+
+// Color scheme: a built-in theme, color overrides and the terminal's color support
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub name: Option<String>,
+    pub colors: Option<String>,
+    pub overrides: HashMap<String, String>,
+}
+
+// Task list: the sort used until another one is chosen in the app,
+// for example "priority desc, then due asc, then title", and tasks per page
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ListConfig {
+    pub sort: Option<String>,
+    pub page_size: Option<usize>,
+}
+
+// Reminders: how often they are checked, in seconds, and an optional command
+// run by `remind` mode for every due reminder instead of printing it
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct RemindersConfig {
+    pub check_interval_seconds: Option<u64>,
+    pub hook: Option<String>,
+}
+
+// Pomodoro: lengths of the work phase and the break, in minutes
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct PomodoroConfig {
+    pub work_minutes: Option<u64>,
+    pub break_minutes: Option<u64>,
+}
+
+// Planning: the estimated work that fits into a week, for example "40h" or "20pts"
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct PlanningConfig {
+    pub weekly_capacity: Option<String>,
+}
+
+// Focus view: how much priority, closeness to the due date and age add to a task's score
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct FocusConfig {
+    pub priority_weight: Option<f64>,
+    pub due_weight: Option<f64>,
+    pub age_weight: Option<f64>,
+}
+
+// Attachments: the command that opens a file or URL, for example "xdg-open" or "firefox"
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct AttachmentsConfig {
+    pub opener: Option<String>,
+}
+
+// A single key or a list of keys bound to the same action
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    pub fn keys(&self) -> Vec<&str> {
+        match self {
+            KeyList::One(key) => vec![key.as_str()],
+            KeyList::Many(keys) => keys.iter().map(|key| key.as_str()).collect(),
+        }
+    }
+}
+
+impl Config {
+    // Read the config file; a missing file means the default configuration
+    pub fn load(path: &Path) -> Result<Config, Box<dyn Error>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e).into()),
+        };
+
+        toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e).into())
+    }
+}
@@ -0,0 +1,95 @@
+// Modal yes/no question asked before destructive actions
+use chrono::{DateTime, Utc};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::app::models::{Attachment, Priority};
+use crate::app::theme::Theme;
+
+// What will be done once the user answers yes
+// Holds the ids of the tasks listed in the dialog, so exactly those are affected
+#[derive(Clone, PartialEq, Debug)]
+pub enum ConfirmedAction {
+    // Move the tasks to the trash
+    DeleteTasks(Vec<i32>),
+    // Remove the tasks in the trash for good
+    PurgeTasks(Vec<i32>),
+    // Bulk changes, asked for when they apply to more than one task
+    SetPriority { task_ids: Vec<i32>, priority: Priority },
+    Reschedule { task_ids: Vec<i32>, due_date: DateTime<Utc> },
+    Tag { task_ids: Vec<i32>, added: Vec<String>, removed: Vec<String> },
+    SetCompleted { task_ids: Vec<i32>, completed: bool },
+    RemoveAttachment { task_id: i32, attachment: Attachment },
+    DeleteTemplate(i32),
+}
+
+impl ConfirmedAction {
+    pub fn task_ids(&self) -> &[i32] {
+        match self {
+            ConfirmedAction::DeleteTasks(task_ids)
+            | ConfirmedAction::PurgeTasks(task_ids)
+            | ConfirmedAction::SetPriority { task_ids, .. }
+            | ConfirmedAction::Reschedule { task_ids, .. }
+            | ConfirmedAction::Tag { task_ids, .. }
+            | ConfirmedAction::SetCompleted { task_ids, .. } => task_ids,
+            ConfirmedAction::RemoveAttachment { task_id, .. } => std::slice::from_ref(task_id),
+            ConfirmedAction::DeleteTemplate(_) => &[],
+        }
+    }
+}
+
+pub struct Confirmation {
+    pub action: ConfirmedAction,
+    pub question: String,
+    // Titles of the affected tasks
+    pub subjects: Vec<String>,
+}
+
+impl Confirmation {
+    pub fn new(action: ConfirmedAction, question: &str, subjects: Vec<String>) -> Confirmation {
+        Confirmation {
+            action,
+            question: question.to_string(),
+            subjects,
+        }
+    }
+}
+
+// Most affected tasks listed in the dialog; the rest are counted
+const LISTED_SUBJECTS: usize = 5;
+
+// Draw the question in a box in the middle of the given area
+pub fn draw_confirmation(f: &mut Frame, area: Rect, confirmation: &Confirmation, theme: &Theme) {
+    let mut lines = vec![Line::from(Span::styled(
+        confirmation.question.as_str(),
+        theme.error,
+    ))];
+    for subject in confirmation.subjects.iter().take(LISTED_SUBJECTS) {
+        lines.push(Line::from(format!("  {}", subject)));
+    }
+    if confirmation.subjects.len() > LISTED_SUBJECTS {
+        lines.push(Line::from(format!(
+            "  ... and {} more",
+            confirmation.subjects.len() - LISTED_SUBJECTS
+        )));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from("y - yes, n/Esc - no"));
+
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let width = (area.width * 3 / 4).max(30).min(area.width);
+    let dialog_area = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    f.render_widget(Clear, dialog_area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(Block::new().borders(Borders::ALL).title("Confirm"))
+            .style(theme.text)
+            .wrap(Wrap { trim: false }),
+        dialog_area,
+    );
+}
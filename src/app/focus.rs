@@ -0,0 +1,104 @@
+// "Today" focus view: open tasks ranked by a score of priority, due date and age
+use chrono::{DateTime, Utc};
+
+use crate::app::config::FocusConfig;
+use crate::app::models::{Priority, Task};
+
+// Days before the due date from which a task starts to get due points,
+// and days after it from which being later does not add more
+const DUE_HORIZON_DAYS: f64 = 14.0;
+// Age at which a task gets all its age points
+const FULL_AGE_DAYS: f64 = 30.0;
+
+// How much each part adds to the score at most, unless configured otherwise
+const DEFAULT_PRIORITY_WEIGHT: f64 = 3.0;
+const DEFAULT_DUE_WEIGHT: f64 = 2.0;
+const DEFAULT_AGE_WEIGHT: f64 = 1.0;
+
+pub struct FocusWeights {
+    pub priority: f64,
+    pub due: f64,
+    pub age: f64,
+}
+
+impl FocusWeights {
+    pub fn from_config(config: &FocusConfig) -> FocusWeights {
+        FocusWeights {
+            priority: config.priority_weight.unwrap_or(DEFAULT_PRIORITY_WEIGHT),
+            due: config.due_weight.unwrap_or(DEFAULT_DUE_WEIGHT),
+            age: config.age_weight.unwrap_or(DEFAULT_AGE_WEIGHT),
+        }
+    }
+}
+
+// Parts of a task's score and what they are based on
+pub struct ScoreBreakdown {
+    pub priority: f64,
+    pub due: f64,
+    pub age: f64,
+    // Negative when the task is late
+    days_until_due: i64,
+    age_days: Option<i64>,
+}
+
+impl ScoreBreakdown {
+    pub fn total(&self) -> f64 {
+        self.priority + self.due + self.age
+    }
+
+    // For example "score 4.10: priority 2.25, due 1.29 (4 days late), age 0.57 (17 days old)"
+    pub fn describe(&self) -> String {
+        let due = match self.days_until_due {
+            0 => "due today".to_string(),
+            days if days < 0 => format!("{} days late", -days),
+            days => format!("due in {} days", days),
+        };
+        let age = match self.age_days {
+            Some(days) => format!("{} days old", days),
+            None => "age unknown".to_string(),
+        };
+        format!(
+            "score {:.2}: priority {:.2}, due {:.2} ({}), age {:.2} ({})",
+            self.total(),
+            self.priority,
+            self.due,
+            due,
+            self.age,
+            age
+        )
+    }
+}
+
+// Each part is scaled to 0..1 before it is weighted
+// The due part is half full on the due date and keeps growing while the task is late
+pub fn score(task: &Task, weights: &FocusWeights, now: DateTime<Utc>) -> ScoreBreakdown {
+    let priority = task.priority.level() as f64 / Priority::Urgent.level() as f64;
+
+    let days_until_due = (task.due_date.date_naive() - now.date_naive()).num_days();
+    let due = if days_until_due < 0 {
+        (1.0 + (-days_until_due as f64 / DUE_HORIZON_DAYS).min(1.0)) / 2.0
+    } else {
+        (1.0 - days_until_due as f64 / DUE_HORIZON_DAYS).max(0.0) / 2.0
+    };
+
+    let age_days = task.created_at.map(|created_at| (now - created_at).num_days());
+    let age = age_days.map_or(0.0, |days| (days as f64 / FULL_AGE_DAYS).min(1.0));
+
+    ScoreBreakdown {
+        priority: weights.priority * priority,
+        due: weights.due * due,
+        age: weights.age * age,
+        days_until_due,
+        age_days,
+    }
+}
+
+// Tasks from the highest score; equal scores keep their order
+pub fn rank(tasks: Vec<Task>, weights: &FocusWeights, now: DateTime<Utc>) -> Vec<Task> {
+    let mut scored = tasks
+        .into_iter()
+        .map(|task| (score(&task, weights, now).total(), task))
+        .collect::<Vec<(f64, Task)>>();
+    scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    scored.into_iter().map(|(_, task)| task).collect()
+}
@@ -0,0 +1,464 @@
+// Mapping of keys to the actions available in the task list
+// The Commands panel is generated from the active keymap
+use crossterm::event::KeyCode;
+
+use crate::app::config::KeysConfig;
+
+// Actions that can be triggered from the task list
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    ToggleCompleted,
+    Add,
+    QuickAdd,
+    SaveTemplate,
+    Edit,
+    Clone,
+    Delete,
+    SortByDueDate,
+    SortByName,
+    SortByPriority,
+    ToggleFocus,
+    ToggleHideCompleted,
+    ToggleDetails,
+    ScrollDetailsDown,
+    ScrollDetailsUp,
+    ShowTrash,
+    ShowArchive,
+    ArchiveCompleted,
+    Restore,
+    Mark,
+    MarkRange,
+    ClearMarks,
+    SetPriority,
+    Reschedule,
+    Tag,
+    AddNote,
+    Attach,
+    OpenAttachment,
+    RemoveAttachment,
+    Snooze,
+    ToggleDeferred,
+    ToggleTimer,
+    TogglePomodoro,
+    BlockedBy,
+    Unblock,
+    ToggleActionable,
+    Next,
+    Previous,
+    NextPage,
+    PreviousPage,
+    Unselect,
+    Quit,
+}
+
+impl Action {
+    // All actions in the order they are listed in the Commands panel
+    pub const ALL: [Action; 42] = [
+        Action::ToggleCompleted,
+        Action::Add,
+        Action::QuickAdd,
+        Action::SaveTemplate,
+        Action::Edit,
+        Action::Clone,
+        Action::Delete,
+        Action::SortByDueDate,
+        Action::SortByName,
+        Action::SortByPriority,
+        Action::ToggleFocus,
+        Action::ToggleHideCompleted,
+        Action::ToggleDetails,
+        Action::ScrollDetailsDown,
+        Action::ScrollDetailsUp,
+        Action::ShowTrash,
+        Action::ShowArchive,
+        Action::ArchiveCompleted,
+        Action::Restore,
+        Action::Mark,
+        Action::MarkRange,
+        Action::ClearMarks,
+        Action::SetPriority,
+        Action::Reschedule,
+        Action::Tag,
+        Action::AddNote,
+        Action::Attach,
+        Action::OpenAttachment,
+        Action::RemoveAttachment,
+        Action::Snooze,
+        Action::ToggleDeferred,
+        Action::ToggleTimer,
+        Action::TogglePomodoro,
+        Action::BlockedBy,
+        Action::Unblock,
+        Action::ToggleActionable,
+        Action::Next,
+        Action::Previous,
+        Action::NextPage,
+        Action::PreviousPage,
+        Action::Unselect,
+        Action::Quit,
+    ];
+
+    // Name of the action as used in the config file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::ToggleCompleted => "toggle_completed",
+            Action::Add => "add",
+            Action::QuickAdd => "quick_add",
+            Action::SaveTemplate => "save_template",
+            Action::Edit => "edit",
+            Action::Clone => "clone",
+            Action::Delete => "delete",
+            Action::SortByDueDate => "sort_by_due_date",
+            Action::SortByName => "sort_by_name",
+            Action::SortByPriority => "sort_by_priority",
+            Action::ToggleFocus => "toggle_focus",
+            Action::ToggleHideCompleted => "toggle_hide_completed",
+            Action::ToggleDetails => "toggle_details",
+            Action::ScrollDetailsDown => "scroll_details_down",
+            Action::ScrollDetailsUp => "scroll_details_up",
+            Action::ShowTrash => "show_trash",
+            Action::ShowArchive => "show_archive",
+            Action::ArchiveCompleted => "archive_completed",
+            Action::Restore => "restore",
+            Action::Mark => "mark",
+            Action::MarkRange => "mark_range",
+            Action::ClearMarks => "clear_marks",
+            Action::SetPriority => "set_priority",
+            Action::Reschedule => "reschedule",
+            Action::Tag => "tag",
+            Action::AddNote => "add_note",
+            Action::Attach => "attach",
+            Action::OpenAttachment => "open_attachment",
+            Action::RemoveAttachment => "remove_attachment",
+            Action::Snooze => "snooze",
+            Action::ToggleDeferred => "toggle_deferred",
+            Action::ToggleTimer => "toggle_timer",
+            Action::TogglePomodoro => "toggle_pomodoro",
+            Action::BlockedBy => "blocked_by",
+            Action::Unblock => "unblock",
+            Action::ToggleActionable => "toggle_actionable",
+            Action::Next => "next",
+            Action::Previous => "previous",
+            Action::NextPage => "next_page",
+            Action::PreviousPage => "previous_page",
+            Action::Unselect => "unselect",
+            Action::Quit => "quit",
+        }
+    }
+
+    // Human readable description shown in the Commands panel
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::ToggleCompleted => "toggle do/done (marked tasks too)",
+            Action::Add => "add a task",
+            Action::QuickAdd => "add a task from one line",
+            Action::SaveTemplate => "save the task as a template",
+            Action::Edit => "edit a task",
+            Action::Clone => "copy a task into a new one",
+            Action::Delete => "delete tasks (purge in trash)",
+            Action::SortByDueDate => "sort by due date",
+            Action::SortByName => "sort by name",
+            Action::SortByPriority => "sort by priority",
+            Action::ToggleFocus => "today view: rank by score/sort",
+            Action::ToggleHideCompleted => "hide/show completed",
+            Action::ToggleDetails => "show commands/task details",
+            Action::ScrollDetailsDown => "scroll the details down",
+            Action::ScrollDetailsUp => "scroll the details up",
+            Action::ShowTrash => "show/leave trash",
+            Action::ShowArchive => "show/leave archive",
+            Action::ArchiveCompleted => "archive tasks completed before a date",
+            Action::Restore => "restore from trash/archive",
+            Action::Mark => "mark/unmark a task",
+            Action::MarkRange => "mark up to the selected task",
+            Action::ClearMarks => "clear marks",
+            Action::SetPriority => "set priority",
+            Action::Reschedule => "set due date",
+            Action::Tag => "add/remove tags",
+            Action::AddNote => "add a note to the task",
+            Action::Attach => "attach a file or link",
+            Action::OpenAttachment => "open an attachment",
+            Action::RemoveAttachment => "remove an attachment",
+            Action::Snooze => "snooze until a later day",
+            Action::ToggleDeferred => "show/hide deferred tasks",
+            Action::ToggleTimer => "start/stop the timer",
+            Action::TogglePomodoro => "start/stop a pomodoro",
+            Action::BlockedBy => "make a task wait for the marked ones",
+            Action::Unblock => "remove what a task waits for",
+            Action::ToggleActionable => "show only actionable/all tasks",
+            Action::Next => "next task",
+            Action::Previous => "previous task",
+            Action::NextPage => "next page",
+            Action::PreviousPage => "previous page",
+            Action::Unselect => "unselect",
+            Action::Quit => "quit",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        // Config files written before the detail pane use the old name
+        let name = if name == "toggle_history" { "toggle_details" } else { name };
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+pub struct KeyMap {
+    bindings: Vec<(KeyCode, Action)>,
+}
+
+impl KeyMap {
+    // The original bindings of the application
+    pub fn default_preset() -> KeyMap {
+        KeyMap {
+            bindings: vec![
+                (KeyCode::Enter, Action::ToggleCompleted),
+                (KeyCode::Char('a'), Action::Add),
+                (KeyCode::Char('n'), Action::QuickAdd),
+                (KeyCode::Char('m'), Action::SaveTemplate),
+                (KeyCode::Char('e'), Action::Edit),
+                (KeyCode::Char('D'), Action::Clone),
+                (KeyCode::Char('x'), Action::Delete),
+                (KeyCode::Char('d'), Action::SortByDueDate),
+                (KeyCode::Char('f'), Action::SortByName),
+                (KeyCode::Char('g'), Action::SortByPriority),
+                (KeyCode::Char('F'), Action::ToggleFocus),
+                (KeyCode::Char('c'), Action::ToggleHideCompleted),
+                (KeyCode::Char('H'), Action::ToggleDetails),
+                (KeyCode::Char(']'), Action::ScrollDetailsDown),
+                (KeyCode::Char('['), Action::ScrollDetailsUp),
+                (KeyCode::Char('t'), Action::ShowTrash),
+                (KeyCode::Char('z'), Action::ShowArchive),
+                (KeyCode::Char('Z'), Action::ArchiveCompleted),
+                (KeyCode::Char('r'), Action::Restore),
+                (KeyCode::Char(' '), Action::Mark),
+                (KeyCode::Char('V'), Action::MarkRange),
+                (KeyCode::Esc, Action::ClearMarks),
+                (KeyCode::Char('p'), Action::SetPriority),
+                (KeyCode::Char('R'), Action::Reschedule),
+                (KeyCode::Char('#'), Action::Tag),
+                (KeyCode::Char('N'), Action::AddNote),
+                (KeyCode::Char('L'), Action::Attach),
+                (KeyCode::Char('O'), Action::OpenAttachment),
+                (KeyCode::Char('U'), Action::RemoveAttachment),
+                (KeyCode::Char('s'), Action::Snooze),
+                (KeyCode::Char('S'), Action::ToggleDeferred),
+                (KeyCode::Char('T'), Action::ToggleTimer),
+                (KeyCode::Char('P'), Action::TogglePomodoro),
+                (KeyCode::Char('b'), Action::BlockedBy),
+                (KeyCode::Char('B'), Action::Unblock),
+                (KeyCode::Char('A'), Action::ToggleActionable),
+                (KeyCode::Down, Action::Next),
+                (KeyCode::Up, Action::Previous),
+                (KeyCode::PageDown, Action::NextPage),
+                (KeyCode::PageUp, Action::PreviousPage),
+                (KeyCode::Left, Action::Unselect),
+                (KeyCode::Char('q'), Action::Quit),
+            ],
+        }
+    }
+
+    // Vim-like navigation; arrows keep working
+    pub fn vim_preset() -> KeyMap {
+        KeyMap {
+            bindings: vec![
+                (KeyCode::Enter, Action::ToggleCompleted),
+                (KeyCode::Char('o'), Action::Add),
+                (KeyCode::Char('n'), Action::QuickAdd),
+                (KeyCode::Char('m'), Action::SaveTemplate),
+                (KeyCode::Char('i'), Action::Edit),
+                (KeyCode::Char('D'), Action::Clone),
+                (KeyCode::Char('x'), Action::Delete),
+                (KeyCode::Char('d'), Action::SortByDueDate),
+                (KeyCode::Char('f'), Action::SortByName),
+                (KeyCode::Char('g'), Action::SortByPriority),
+                (KeyCode::Char('F'), Action::ToggleFocus),
+                (KeyCode::Char('c'), Action::ToggleHideCompleted),
+                (KeyCode::Char('H'), Action::ToggleDetails),
+                (KeyCode::Char(']'), Action::ScrollDetailsDown),
+                (KeyCode::Char('['), Action::ScrollDetailsUp),
+                (KeyCode::Char('t'), Action::ShowTrash),
+                (KeyCode::Char('z'), Action::ShowArchive),
+                (KeyCode::Char('Z'), Action::ArchiveCompleted),
+                (KeyCode::Char('r'), Action::Restore),
+                (KeyCode::Char(' '), Action::Mark),
+                (KeyCode::Char('V'), Action::MarkRange),
+                (KeyCode::Esc, Action::ClearMarks),
+                (KeyCode::Char('p'), Action::SetPriority),
+                (KeyCode::Char('R'), Action::Reschedule),
+                (KeyCode::Char('#'), Action::Tag),
+                (KeyCode::Char('N'), Action::AddNote),
+                (KeyCode::Char('L'), Action::Attach),
+                (KeyCode::Char('O'), Action::OpenAttachment),
+                (KeyCode::Char('U'), Action::RemoveAttachment),
+                (KeyCode::Char('s'), Action::Snooze),
+                (KeyCode::Char('S'), Action::ToggleDeferred),
+                (KeyCode::Char('T'), Action::ToggleTimer),
+                (KeyCode::Char('P'), Action::TogglePomodoro),
+                (KeyCode::Char('b'), Action::BlockedBy),
+                (KeyCode::Char('B'), Action::Unblock),
+                (KeyCode::Char('A'), Action::ToggleActionable),
+                (KeyCode::Char('j'), Action::Next),
+                (KeyCode::Down, Action::Next),
+                (KeyCode::Char('k'), Action::Previous),
+                (KeyCode::Up, Action::Previous),
+                (KeyCode::Char('J'), Action::NextPage),
+                (KeyCode::PageDown, Action::NextPage),
+                (KeyCode::Char('K'), Action::PreviousPage),
+                (KeyCode::PageUp, Action::PreviousPage),
+                (KeyCode::Char('h'), Action::Unselect),
+                (KeyCode::Left, Action::Unselect),
+                (KeyCode::Char('q'), Action::Quit),
+            ],
+        }
+    }
+
+    // Build the keymap from the preset and overrides of the config file
+    pub fn from_config(config: &KeysConfig) -> Result<KeyMap, String> {
+        let mut keymap = match config.preset.as_deref() {
+            None | Some("default") => KeyMap::default_preset(),
+            Some("vim") => KeyMap::vim_preset(),
+            Some(other) => return Err(format!("Unknown keymap preset '{}'", other)),
+        };
+
+        // A key given to two actions is refused, the winner would depend on the map's order
+        let mut names = config.bindings.keys().collect::<Vec<&String>>();
+        names.sort();
+        let mut overrides: Vec<(&str, Action, Vec<KeyCode>)> = Vec::new();
+        for name in names {
+            let action =
+                Action::from_name(name).ok_or(format!("Unknown action '{}' in keymap", name))?;
+            if let Some((other, _, _)) = overrides.iter().find(|(_, bound, _)| *bound == action) {
+                return Err(format!("'{}' and '{}' are the same action in keymap", other, name));
+            }
+            let mut key_codes = Vec::new();
+            for key in config.bindings[name].keys() {
+                let key_code = parse_key(key).ok_or(format!("Unknown key '{}' for '{}'", key, name))?;
+                if let Some((other, _, _)) = overrides.iter().find(|(_, _, keys)| keys.contains(&key_code)) {
+                    return Err(format!("Key '{}' is bound to both '{}' and '{}'", key, other, name));
+                }
+                if !key_codes.contains(&key_code) {
+                    key_codes.push(key_code);
+                }
+            }
+            overrides.push((name, action, key_codes));
+        }
+        for (_, action, key_codes) in overrides {
+            keymap.bind(action, key_codes);
+        }
+
+        Ok(keymap)
+    }
+
+    // Replace all keys of the action; the keys are taken away from other actions
+    fn bind(&mut self, action: Action, keys: Vec<KeyCode>) {
+        self.bindings
+            .retain(|(key, bound)| *bound != action && !keys.contains(key));
+        self.bindings
+            .extend(keys.into_iter().map(|key| (key, action)));
+    }
+
+    // Find the action bound to the pressed key
+    pub fn action_for(&self, key: KeyCode) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == key)
+            .map(|(_, action)| *action)
+    }
+
+    // Get all keys bound to the action
+    pub fn keys_for(&self, action: Action) -> Vec<KeyCode> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    // Labels of the keys bound to the action, for example "j/Down"
+    pub fn key_labels(&self, action: Action) -> String {
+        self.keys_for(action)
+            .into_iter()
+            .map(key_label)
+            .collect::<Vec<String>>()
+            .join("/")
+    }
+}
+
+// Parse a key as written in the config file: a single char or a key name
+pub fn parse_key(key: &str) -> Option<KeyCode> {
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+
+    match key.to_lowercase().as_str() {
+        "enter" => Some(KeyCode::Enter),
+        "esc" => Some(KeyCode::Esc),
+        "space" => Some(KeyCode::Char(' ')),
+        "tab" => Some(KeyCode::Tab),
+        "backspace" => Some(KeyCode::Backspace),
+        "delete" => Some(KeyCode::Delete),
+        "up" => Some(KeyCode::Up),
+        "down" => Some(KeyCode::Down),
+        "left" => Some(KeyCode::Left),
+        "right" => Some(KeyCode::Right),
+        "home" => Some(KeyCode::Home),
+        "end" => Some(KeyCode::End),
+        "pageup" => Some(KeyCode::PageUp),
+        "pagedown" => Some(KeyCode::PageDown),
+        _ => None,
+    }
+}
+
+// Label of the key as shown in the Commands panel
+pub fn key_label(key: KeyCode) -> String {
+    match key {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::PageUp => "PgUp".to_string(),
+        KeyCode::PageDown => "PgDn".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::config::KeyList;
+
+    fn keys_config(bindings: &[(&str, &[&str])]) -> KeysConfig {
+        KeysConfig {
+            preset: None,
+            bindings: bindings
+                .iter()
+                .map(|(name, keys)| {
+                    let keys = keys.iter().map(|key| key.to_string()).collect();
+                    (name.to_string(), KeyList::Many(keys))
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn overrides_take_keys_from_the_preset() {
+        let keymap = KeyMap::from_config(&keys_config(&[("delete", &["D"]), ("next", &["j", "down"])])).unwrap();
+        assert_eq!(keymap.action_for(KeyCode::Char('D')), Some(Action::Delete));
+        assert_eq!(keymap.action_for(KeyCode::Char('x')), None);
+        assert_eq!(keymap.keys_for(Action::Next), vec![KeyCode::Char('j'), KeyCode::Down]);
+    }
+
+    #[test]
+    fn rejects_a_key_bound_to_two_actions() {
+        for _ in 0..10 {
+            let error = KeyMap::from_config(&keys_config(&[("delete", &["D"]), ("edit", &["e", "D"])]));
+            assert_eq!(error.err(), Some("Key 'D' is bound to both 'delete' and 'edit'".to_string()));
+        }
+    }
+
+    #[test]
+    fn rejects_the_old_and_new_name_of_an_action_together() {
+        let error = KeyMap::from_config(&keys_config(&[("toggle_details", &["H"]), ("toggle_history", &["Y"])]));
+        assert!(error.is_err());
+        let keymap = KeyMap::from_config(&keys_config(&[("toggle_history", &["Y"])])).unwrap();
+        assert_eq!(keymap.action_for(KeyCode::Char('Y')), Some(Action::ToggleDetails));
+    }
+}
@@ -1,5 +1,20 @@
+mod attachments;
+pub mod config;
+mod confirm;
+mod focus;
+mod keymap;
 mod models;
+mod pomodoro;
+mod prompt;
+mod query;
+mod quick_add;
+pub mod reminders;
+mod sort;
+mod statistics;
 pub mod storage;
 mod task_edit;
 mod task_list;
+mod template;
+mod theme;
+pub mod timesheet;
 pub mod ui;
@@ -1,10 +1,435 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
 
+// Dates are entered and shown as dd.mm.yyyy
+pub const DATE_FORMAT: &str = "%d.%m.%Y";
+
+// Parse an entered date as the beginning of that day in UTC
+pub fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    NaiveDate::parse_from_str(date.trim(), DATE_FORMAT)
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date| date.and_utc())
+}
+
+// Snooze presets: "1" or "tomorrow", "2" or "next week" (the coming Monday), or a date
+// "0" or "none" ends the snooze, so the task shows up right away
+pub fn parse_snooze(input: &str, now: DateTime<Utc>) -> Option<Option<DateTime<Utc>>> {
+    let today = now.date_naive();
+    let day = match input.trim().to_lowercase().as_str() {
+        "0" | "none" => return Some(None),
+        "1" | "tomorrow" => today + Duration::days(1),
+        "2" | "next week" => today + Duration::days(7 - today.weekday().num_days_from_monday() as i64),
+        date => return parse_date(date).map(Some),
+    };
+    day.and_hms_opt(0, 0, 0).map(|day| Some(day.and_utc()))
+}
+
+// Tags are entered as words separated by spaces or commas, optionally starting with #
+// They are stored lowercase and without the #
+pub fn parse_tags(tags: &str) -> Vec<String> {
+    let mut parsed = Vec::new();
+    for tag in tags.split(|c: char| c.is_whitespace() || c == ',') {
+        let tag = tag.trim_start_matches('#').to_lowercase();
+        if !tag.is_empty() && !parsed.contains(&tag) {
+            parsed.push(tag);
+        }
+    }
+    parsed.sort();
+    parsed
+}
+
+// Tags as shown to the user, for example "#home #errands"
+pub fn format_tags(tags: &[String]) -> String {
+    tags.iter()
+        .map(|tag| format!("#{}", tag))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Longest offset accepted, about 100 years; longer ones would overflow the date arithmetic
+const MAX_OFFSET_MINUTES: i64 = 100 * 365 * 24 * 60;
+
+// Reminder offsets are entered as a number with a unit, for example "30m", "2h", "1d" or "1w"
+// "0" means at the due time
+pub fn parse_offset(offset: &str) -> Option<Duration> {
+    let offset = offset.trim().to_lowercase();
+    if offset == "0" {
+        return Some(Duration::zero());
+    }
+
+    let (unit_start, _) = offset.char_indices().last()?;
+    let (amount, unit) = offset.split_at(unit_start);
+    let amount = amount.parse::<i64>().ok().filter(|amount| *amount >= 0)?;
+    let unit_minutes = match unit {
+        "m" => 1,
+        "h" => 60,
+        "d" => 24 * 60,
+        "w" => 7 * 24 * 60,
+        _ => return None,
+    };
+    amount
+        .checked_mul(unit_minutes)
+        .filter(|minutes| *minutes <= MAX_OFFSET_MINUTES)
+        .map(Duration::minutes)
+}
+
+// Offset in the largest unit that represents it exactly
+pub fn format_offset(offset: Duration) -> String {
+    let minutes = offset.num_minutes();
+    if minutes == 0 {
+        "0".to_string()
+    } else if minutes % (7 * 24 * 60) == 0 {
+        format!("{}w", minutes / (7 * 24 * 60))
+    } else if minutes % (24 * 60) == 0 {
+        format!("{}d", minutes / (24 * 60))
+    } else if minutes % 60 == 0 {
+        format!("{}h", minutes / 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+// Reminders entered as offsets separated by spaces or commas, for example "1d 2h 0"
+// Sorted from the earliest reminder, without duplicates
+pub fn parse_reminders(reminders: &str) -> Option<Vec<Duration>> {
+    let mut parsed = Vec::new();
+    for offset in reminders
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|offset| !offset.is_empty())
+    {
+        let offset = parse_offset(offset)?;
+        if !parsed.contains(&offset) {
+            parsed.push(offset);
+        }
+    }
+    parsed.sort_by(|a, b| b.cmp(a));
+    Some(parsed)
+}
+
+pub fn format_reminders(reminders: &[Duration]) -> String {
+    reminders
+        .iter()
+        .map(|offset| format_offset(*offset))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Expected effort of a task: time, or story points for teams that plan with them
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Estimate {
+    Time(Duration),
+    Points(u32),
+}
+
+impl Estimate {
+    // Time like "30m" or "2h", or points like "3pts" (also "3p" or "3pt")
+    // Days and weeks are refused: a "1d" of 24 hours would not compare well with a capacity in working hours
+    pub fn parse(estimate: &str) -> Option<Estimate> {
+        let estimate = estimate.replace(' ', "").to_lowercase();
+        let points = ["pts", "pt", "p"]
+            .iter()
+            .find_map(|suffix| estimate.strip_suffix(suffix));
+        match points {
+            Some(points) => points.parse().ok().map(Estimate::Points),
+            None => Some(&estimate)
+                .filter(|estimate| estimate.ends_with('m') || estimate.ends_with('h'))
+                .and_then(|estimate| parse_offset(estimate))
+                .filter(|time| !time.is_zero())
+                .map(Estimate::Time),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Estimate::Time(time) if time.num_minutes() % 60 == 0 => format!("{}h", time.num_hours()),
+            Estimate::Time(time) => format!("{}m", time.num_minutes()),
+            Estimate::Points(points) => format!("{}pts", points),
+        }
+    }
+}
+
+// Estimates of several tasks; time and points are added up separately
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct EstimateTotal {
+    pub time: Duration,
+    pub points: u64,
+}
+
+impl EstimateTotal {
+    // Whether the total is over the limit of the same kind
+    pub fn exceeds(&self, limit: Estimate) -> bool {
+        match limit {
+            Estimate::Time(time) => self.time > time,
+            Estimate::Points(points) => self.points > points as u64,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match (self.time.is_zero(), self.points) {
+            (true, 0) => "-".to_string(),
+            (false, 0) => format_tracked(self.time),
+            (true, points) => format!("{}pts", points),
+            (false, points) => format!("{} + {}pts", format_tracked(self.time), points),
+        }
+    }
+}
+
+// Tracked time in hours and minutes, for example "2h 05m" or "40m"
+pub fn format_tracked(tracked: Duration) -> String {
+    let minutes = tracked.num_minutes();
+    if minutes >= 60 {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+// Running time as a clock, for example "1:02:09"
+pub fn format_clock(elapsed: Duration) -> String {
+    let seconds = elapsed.num_seconds().max(0);
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+#[derive(Default, Clone)]
 pub struct Task {
     pub id: Option<i32>,
     pub title: String,
     pub description: String,
     pub due_date: DateTime<Utc>,
-    pub priority: i32,
+    pub priority: Priority,
     pub completed: bool,
+    // Sorted, without duplicates
+    pub tags: Vec<String>,
+    // How long before the due date to remind, sorted from the earliest reminder
+    pub reminders: Vec<Duration>,
+    // Time of the finished time entries, none if nothing was tracked yet
+    pub tracked: Option<Duration>,
+    // Waits for a task that is still open
+    pub blocked: bool,
+    pub estimate: Option<Estimate>,
+    // Hidden from the list until this day
+    pub defer_until: Option<DateTime<Utc>>,
+    pub latest_note: Option<TaskNote>,
+    // Set by the storage; unknown for tasks created before they were tracked
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub archived_at: Option<DateTime<Utc>>,
+    // Stored row version the task was read at
+    pub version: i64,
+}
+
+// Entry of the notes log of a task; notes are only ever added
+#[derive(Clone)]
+pub struct TaskNote {
+    pub created_at: DateTime<Utc>,
+    pub text: String,
+}
+
+// File path or URL attached to a task
+#[derive(Clone, PartialEq, Debug)]
+pub struct Attachment {
+    pub id: Option<i64>,
+    pub target: String,
+    pub label: String,
+}
+
+// One entry of the task history
+pub struct TaskChange {
+    pub changed_at: DateTime<Utc>,
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+// A reminder whose time has come, with the task it belongs to
+pub struct DueReminder {
+    pub reminder_id: i64,
+    pub offset: Duration,
+    pub task_id: i32,
+    pub title: String,
+    pub due_date: DateTime<Utc>,
+}
+
+// The open time entry; at most one timer runs at a time
+pub struct RunningTimer {
+    pub task_id: i32,
+    pub title: String,
+    pub started_at: DateTime<Utc>,
+}
+
+// Time tracked on a task on one day
+pub struct TimesheetRow {
+    pub day: NaiveDate,
+    pub task_id: i32,
+    pub title: String,
+    pub tracked: Duration,
+}
+
+// Named starting point for tasks created again and again, for example a release checklist
+pub struct TaskTemplate {
+    pub id: Option<i32>,
+    pub name: String,
+    pub title: String,
+    pub description: String,
+    pub priority: Priority,
+    // The task is due this many days after the day it is created
+    pub due_offset_days: i64,
+    // Titles of the tasks created along with the task, which then waits for them
+    pub subtasks: Vec<String>,
+}
+
+// Summary of the completed tasks with known completion time
+pub struct CompletionSummary {
+    pub completed: u64,
+    // Completed no later than on the due date
+    pub on_time: u64,
+    // Average time from creation to completion
+    pub average_lead_time: Option<Duration>,
+}
+
+// Importance of a task, ordered from the least to the most important
+// Stored in the DB as an integer level (0 - none ... 4 - urgent)
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum Priority {
+    #[default]
+    None,
+    Low,
+    Medium,
+    High,
+    Urgent,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 5] = [
+        Priority::None,
+        Priority::Low,
+        Priority::Medium,
+        Priority::High,
+        Priority::Urgent,
+    ];
+
+    // Read any stored integer; values out of range are clamped
+    pub fn from_level(level: i64) -> Priority {
+        Priority::ALL[level.clamp(0, 4) as usize]
+    }
+
+    pub fn level(&self) -> i64 {
+        *self as i64
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Priority::None => "None",
+            Priority::Low => "Low",
+            Priority::Medium => "Medium",
+            Priority::High => "High",
+            Priority::Urgent => "Urgent",
+        }
+    }
+
+    // Parse a priority entered by its label or level, for example "high" or "3"
+    pub fn parse(priority: &str) -> Option<Priority> {
+        let priority = priority.trim().to_lowercase();
+        Priority::ALL.into_iter().find(|candidate| {
+            candidate.label().to_lowercase() == priority || candidate.level().to_string() == priority
+        })
+    }
+
+    // The next more important priority; stays at urgent
+    pub fn raised(&self) -> Priority {
+        Priority::from_level(self.level() + 1)
+    }
+
+    // The next less important priority; stays at none
+    pub fn lowered(&self) -> Priority {
+        Priority::from_level(self.level() - 1)
+    }
+}
+
+impl ToSql for Priority {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.level()))
+    }
+}
+
+impl FromSql for Priority {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        i64::column_result(value).map(Priority::from_level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_offsets_in_each_unit() {
+        assert_eq!(parse_offset("0"), Some(Duration::zero()));
+        assert_eq!(parse_offset("30m"), Some(Duration::minutes(30)));
+        assert_eq!(parse_offset(" 2H "), Some(Duration::hours(2)));
+        assert_eq!(parse_offset("1d"), Some(Duration::days(1)));
+        assert_eq!(parse_offset("3w"), Some(Duration::weeks(3)));
+    }
+
+    #[test]
+    fn rejects_invalid_offsets() {
+        for offset in ["", "m", "5", "5x", "-5m", "1.5h", "5ü", "ü", "h5"] {
+            assert_eq!(parse_offset(offset), None, "{}", offset);
+        }
+    }
+
+    #[test]
+    fn rejects_offsets_that_would_overflow() {
+        assert_eq!(parse_offset("9999999999999999w"), None);
+        assert_eq!(parse_offset("9223372036854775807m"), None);
+        assert_eq!(parse_offset("99999999999999999999d"), None);
+        assert_eq!(parse_offset("5000w"), Some(Duration::weeks(5000)));
+        assert_eq!(parse_offset("5300000w"), None);
+    }
+
+    #[test]
+    fn parses_estimates_as_time_or_points() {
+        assert_eq!(Estimate::parse("2h"), Some(Estimate::Time(Duration::hours(2))));
+        assert_eq!(Estimate::parse(" 90 M "), Some(Estimate::Time(Duration::minutes(90))));
+        assert_eq!(Estimate::parse("3pts"), Some(Estimate::Points(3)));
+        assert_eq!(Estimate::parse("5P"), Some(Estimate::Points(5)));
+        for estimate in ["", "0", "3x", "-2h", "2ü", "ü", "pts", "1d", "2w", "9999999999999999h"] {
+            assert_eq!(Estimate::parse(estimate), None, "{}", estimate);
+        }
+        assert_eq!(Estimate::Time(Duration::hours(24)).label(), "24h");
+        assert_eq!(Estimate::Time(Duration::minutes(90)).label(), "90m");
+    }
+
+    #[test]
+    fn parses_reminders_sorted_without_duplicates() {
+        assert_eq!(
+            parse_reminders("0, 2h 1d 120m"),
+            Some(vec![Duration::days(1), Duration::hours(2), Duration::zero()])
+        );
+        assert_eq!(parse_reminders(""), Some(Vec::new()));
+        assert_eq!(parse_reminders("1d 5ü"), None);
+        assert_eq!(parse_reminders("1d 9999999999999999w"), None);
+    }
+
+    #[test]
+    fn formats_offsets_in_the_largest_exact_unit() {
+        assert_eq!(format_offset(Duration::zero()), "0");
+        assert_eq!(format_offset(Duration::minutes(90)), "90m");
+        assert_eq!(format_offset(Duration::hours(25)), "25h");
+        assert_eq!(format_offset(Duration::days(14)), "2w");
+        assert_eq!(format_reminders(&[Duration::days(1), Duration::zero()]), "1d 0");
+    }
+
+    #[test]
+    fn formatted_offsets_parse_back() {
+        for minutes in [0, 1, 59, 60, 90, 1440, 1500, 10080, 20160, 30240 + 60] {
+            let offset = Duration::minutes(minutes);
+            assert_eq!(parse_offset(&format_offset(offset)), Some(offset));
+        }
+        let reminders = parse_reminders("1w 3d 2h 45m 0").unwrap();
+        assert_eq!(parse_reminders(&format_reminders(&reminders)), Some(reminders));
+    }
 }
@@ -0,0 +1,118 @@
+// Pomodoro timer bound to a task: a work phase followed by a break
+use chrono::Utc;
+use ratatui::{prelude::*, widgets::*};
+use std::time::{Duration, Instant};
+
+use crate::app::config::PomodoroConfig;
+use crate::app::models::format_clock;
+use crate::app::storage::{write_error, Storage};
+use crate::app::theme::Theme;
+
+// Phase lengths in minutes unless configured otherwise
+const DEFAULT_WORK_MINUTES: u64 = 25;
+const DEFAULT_BREAK_MINUTES: u64 = 5;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PomodoroPhase {
+    Work,
+    Break,
+}
+
+// The pomodoro in progress
+pub struct Pomodoro {
+    pub task_id: i32,
+    pub title: String,
+    pub phase: PomodoroPhase,
+    started_at: Instant,
+}
+
+pub struct PomodoroTimer {
+    work: Duration,
+    rest: Duration,
+    pub current: Option<Pomodoro>,
+}
+
+impl PomodoroTimer {
+    pub fn from_config(config: &PomodoroConfig) -> PomodoroTimer {
+        let minutes =
+            |value: Option<u64>, default| Duration::from_secs(value.unwrap_or(default).max(1) * 60);
+        PomodoroTimer {
+            work: minutes(config.work_minutes, DEFAULT_WORK_MINUTES),
+            rest: minutes(config.break_minutes, DEFAULT_BREAK_MINUTES),
+            current: None,
+        }
+    }
+
+    pub fn start(&mut self, task_id: i32, title: &str) {
+        self.current = Some(Pomodoro {
+            task_id,
+            title: title.to_string(),
+            phase: PomodoroPhase::Work,
+            started_at: Instant::now(),
+        });
+    }
+
+    pub fn stop(&mut self) {
+        self.current = None;
+    }
+
+    fn length(&self, phase: PomodoroPhase) -> Duration {
+        match phase {
+            PomodoroPhase::Work => self.work,
+            PomodoroPhase::Break => self.rest,
+        }
+    }
+
+    // Move to the next phase once the current one is over
+    // A finished work phase is stored as a pomodoro of the task; returns what happened
+    pub fn tick(&mut self, storage: &Storage) -> Option<String> {
+        let length = self.length(self.current.as_ref()?.phase);
+        let pomodoro = self.current.as_mut()?;
+        if pomodoro.started_at.elapsed() < length {
+            return None;
+        }
+
+        match pomodoro.phase {
+            PomodoroPhase::Work => {
+                let saved =
+                    storage.add_pomodoro(pomodoro.task_id, Utc::now(), length.as_secs() as i64 / 60);
+                pomodoro.phase = PomodoroPhase::Break;
+                pomodoro.started_at = Instant::now();
+                Some(match saved {
+                    Ok(_) => format!("Pomodoro done on {}, time for a break", pomodoro.title),
+                    Err(error) => write_error(error),
+                })
+            }
+            PomodoroPhase::Break => {
+                self.current = None;
+                Some("Break is over".to_string())
+            }
+        }
+    }
+}
+
+// Draw the countdown of the current phase
+pub fn draw_pomodoro(f: &mut Frame, area: Rect, timer: &PomodoroTimer, theme: &Theme) {
+    let pomodoro = match &timer.current {
+        Some(pomodoro) => pomodoro,
+        None => return,
+    };
+
+    let length = timer.length(pomodoro.phase);
+    let elapsed = pomodoro.started_at.elapsed().min(length);
+    let left =
+        chrono::Duration::from_std(length - elapsed).unwrap_or_else(|_| chrono::Duration::zero());
+    let title = match pomodoro.phase {
+        PomodoroPhase::Work => format!("Pomodoro: {}", pomodoro.title),
+        PomodoroPhase::Break => "Pomodoro: break".to_string(),
+    };
+
+    f.render_widget(
+        Gauge::default()
+            .block(Block::new().title(title).borders(Borders::ALL))
+            .gauge_style(theme.chart)
+            .ratio(elapsed.as_secs_f64() / length.as_secs_f64())
+            .label(format!("{} left", format_clock(left))),
+        area,
+    );
+}
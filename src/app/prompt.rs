@@ -0,0 +1,94 @@
+// Single line input shown over the task list, for example to enter a date
+use ratatui::{prelude::*, widgets::*};
+
+use crate::app::theme::Theme;
+
+// What the entered text will be used for
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PromptPurpose {
+    ArchiveCompletedBefore,
+    // Bulk operations on the marked or selected tasks
+    SetPriority,
+    Reschedule,
+    Tag,
+    Snooze,
+    QuickAdd,
+    SaveTemplate,
+    AddNote,
+    Attach,
+    OpenAttachment,
+    RemoveAttachment,
+}
+
+pub struct Prompt {
+    pub purpose: PromptPurpose,
+    pub label: String,
+    pub input: String,
+    pub error: Option<String>,
+    // The tasks the entered text applies to, taken when the prompt opens
+    pub task_ids: Vec<i32>,
+}
+
+impl Prompt {
+    pub fn new(purpose: PromptPurpose, label: &str, initial_input: &str) -> Prompt {
+        Prompt {
+            purpose,
+            label: label.to_string(),
+            input: initial_input.to_string(),
+            error: None,
+            task_ids: Vec::new(),
+        }
+    }
+
+    pub fn for_tasks(mut self, task_ids: Vec<i32>) -> Prompt {
+        self.task_ids = task_ids;
+        self
+    }
+
+    // The single task the prompt was opened on
+    pub fn task_id(&self) -> Option<i32> {
+        self.task_ids.first().copied()
+    }
+
+    // Append a char to the end of the input
+    pub fn input(&mut self, to_insert: char) {
+        self.input.push(to_insert);
+        self.error = None;
+    }
+
+    // Delete the last char of the input
+    pub fn delete_char(&mut self) {
+        self.input.pop();
+        self.error = None;
+    }
+}
+
+// Draw the prompt as a box at the bottom of the given area
+pub fn draw_prompt(f: &mut Frame, area: Rect, prompt: &Prompt, theme: &Theme) {
+    let height = if prompt.error.is_some() { 4 } else { 3 };
+    let prompt_area = Rect {
+        x: area.x + 1,
+        y: area.y + area.height.saturating_sub(height + 1),
+        width: area.width.saturating_sub(2),
+        height: height.min(area.height),
+    };
+
+    let mut lines = vec![Line::from(vec![
+        Span::styled(format!("{} ", prompt.label), theme.text),
+        Span::styled(prompt.input.as_str(), theme.text),
+        Span::styled(" ", theme.cursor),
+    ])];
+    if let Some(error) = &prompt.error {
+        lines.push(Line::from(Span::styled(error.as_str(), theme.error)));
+    }
+
+    f.render_widget(Clear, prompt_area);
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::new()
+                .borders(Borders::ALL)
+                .title("Enter - confirm, Esc - cancel"),
+        ),
+        prompt_area,
+    );
+}
@@ -0,0 +1,272 @@
+// Builder of parameterized task queries: filter, sort and paging are done by SQLite
+use chrono::{DateTime, Utc};
+use now::DateTimeNow;
+use rusqlite::ToSql;
+use std::ops::Add;
+
+use crate::app::models::Task;
+use crate::app::sort::{SortDirection, SortSpec, SortedBy};
+
+// Which part of the task archive is looked at
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum TaskScope {
+    // Tasks that are neither deleted nor archived
+    #[default]
+    Active,
+    Trash,
+    Archive,
+}
+
+impl TaskScope {
+    pub fn condition(&self) -> &'static str {
+        match self {
+            TaskScope::Active => "DeletedAt IS NULL AND ArchivedAt IS NULL",
+            TaskScope::Trash => "DeletedAt IS NOT NULL",
+            TaskScope::Archive => "DeletedAt IS NULL AND ArchivedAt IS NOT NULL",
+        }
+    }
+
+    // Same as the condition, for a task already read
+    pub fn contains(&self, task: &Task) -> bool {
+        match self {
+            TaskScope::Active => task.deleted_at.is_none() && task.archived_at.is_none(),
+            TaskScope::Trash => task.deleted_at.is_some(),
+            TaskScope::Archive => task.deleted_at.is_none() && task.archived_at.is_some(),
+        }
+    }
+}
+
+// A task is blocked while one of the tasks it depends on is open and not deleted
+pub const BLOCKED_CONDITION: &str = "EXISTS (SELECT 1 FROM task_dependency \
+    JOIN task_item AS blocker ON blocker.Id = task_dependency.BlockedById \
+    WHERE task_dependency.TaskId = task_item.Id AND blocker.Completed = 0 AND blocker.DeletedAt IS NULL)";
+
+// Conditions a task has to meet; unset fields do not filter
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct TaskFilter {
+    pub scope: TaskScope,
+    pub completed: Option<bool>,
+    pub due_before: Option<DateTime<Utc>>,
+    pub due_after: Option<DateTime<Utc>>,
+    // Only open tasks that are not blocked
+    pub actionable: bool,
+    // Also the active tasks deferred to a later day
+    pub include_deferred: bool,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct TaskQuery {
+    pub filter: TaskFilter,
+    pub sort: SortSpec,
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+impl TaskFilter {
+    pub fn uncompleted() -> TaskFilter {
+        TaskFilter {
+            completed: Some(false),
+            ..TaskFilter::default()
+        }
+    }
+
+    // Uncompleted tasks due within the next week, late ones included
+    pub fn due_next_week() -> TaskFilter {
+        TaskFilter {
+            completed: Some(false),
+            due_before: Some(Utc::now().add(chrono::Duration::weeks(1))),
+            ..TaskFilter::default()
+        }
+    }
+
+    // Uncompleted tasks due from today to a week from now, without the late ones
+    pub fn upcoming() -> TaskFilter {
+        TaskFilter {
+            due_after: Some(Utc::now().beginning_of_day()),
+            ..TaskFilter::due_next_week()
+        }
+    }
+
+    // Uncompleted tasks due before today
+    pub fn late() -> TaskFilter {
+        TaskFilter {
+            completed: Some(false),
+            due_before: Some(Utc::now().beginning_of_day()),
+            ..TaskFilter::default()
+        }
+    }
+
+    // WHERE clause and its parameters
+    pub fn to_sql(&self) -> (String, Vec<Box<dyn ToSql>>) {
+        let not_blocked = format!("NOT {}", BLOCKED_CONDITION);
+        let mut conditions: Vec<&str> = vec![self.scope.condition()];
+        let mut params: Vec<Box<dyn ToSql>> = Vec::new();
+
+        if let Some(completed) = self.completed {
+            conditions.push("Completed = ?");
+            params.push(Box::new(completed));
+        }
+        if let Some(due_before) = self.due_before {
+            conditions.push("DueDate < ?");
+            params.push(Box::new(due_before));
+        }
+        if let Some(due_after) = self.due_after {
+            conditions.push("DueDate >= ?");
+            params.push(Box::new(due_after));
+        }
+        if self.actionable {
+            conditions.push("Completed = 0");
+            conditions.push(&not_blocked);
+        }
+        if self.scope == TaskScope::Active && !self.include_deferred {
+            conditions.push("(DeferUntil IS NULL OR DeferUntil <= ?)");
+            params.push(Box::new(Utc::now()));
+        }
+
+        (format!(" WHERE {}", conditions.join(" AND ")), params)
+    }
+}
+
+impl SortedBy {
+    fn column(&self) -> &'static str {
+        match self {
+            SortedBy::DueDate => "DueDate",
+            SortedBy::Name => "Title",
+            SortedBy::Priority => "PriorityLevel",
+        }
+    }
+}
+
+impl TaskQuery {
+    // Full statement selecting the given columns, and its parameters
+    // Rows equal in all sort keys are ordered by id, so pages are stable
+    pub fn to_sql(&self, columns: &str) -> (String, Vec<Box<dyn ToSql>>) {
+        let (where_clause, mut params) = self.filter.to_sql();
+
+        let mut order_by = self
+            .sort
+            .keys
+            .iter()
+            .map(|key| match key.direction {
+                SortDirection::Ascending => format!("{} ASC", key.by.column()),
+                SortDirection::Descending => format!("{} DESC", key.by.column()),
+            })
+            .collect::<Vec<String>>();
+        order_by.push("Id ASC".to_string());
+
+        let mut sql = format!(
+            "SELECT {} FROM task_item{} ORDER BY {}",
+            columns,
+            where_clause,
+            order_by.join(", ")
+        );
+
+        if let Some(limit) = self.limit {
+            sql.push_str(" LIMIT ? OFFSET ?");
+            params.push(Box::new(limit as i64));
+            params.push(Box::new(self.offset as i64));
+        }
+
+        (sql, params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rusqlite::types::{ToSqlOutput, Value};
+
+    fn value<T: ToSql + ?Sized>(param: &T) -> Value {
+        match param.to_sql().unwrap() {
+            ToSqlOutput::Owned(value) => value,
+            ToSqlOutput::Borrowed(value) => value.into(),
+            other => panic!("Unexpected parameter {:?}", other),
+        }
+    }
+
+    fn values(params: &[Box<dyn ToSql>]) -> Vec<Value> {
+        params.iter().map(|param| value(param.as_ref())).collect()
+    }
+
+    #[test]
+    fn active_tasks_leave_out_the_deferred_ones() {
+        let (sql, params) = TaskFilter::default().to_sql();
+        assert_eq!(
+            sql,
+            " WHERE DeletedAt IS NULL AND ArchivedAt IS NULL AND (DeferUntil IS NULL OR DeferUntil <= ?)"
+        );
+        assert_eq!(params.len(), 1);
+
+        let filter = TaskFilter {
+            include_deferred: true,
+            ..TaskFilter::default()
+        };
+        let (sql, params) = filter.to_sql();
+        assert_eq!(sql, " WHERE DeletedAt IS NULL AND ArchivedAt IS NULL");
+        assert!(params.is_empty());
+    }
+
+    #[test]
+    fn conditions_and_parameters_follow_the_same_order() {
+        let after = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        let before = Utc.with_ymd_and_hms(2026, 1, 8, 0, 0, 0).unwrap();
+        let filter = TaskFilter {
+            scope: TaskScope::Trash,
+            completed: Some(false),
+            due_before: Some(before),
+            due_after: Some(after),
+            ..TaskFilter::default()
+        };
+        let (sql, params) = filter.to_sql();
+        assert_eq!(sql, " WHERE DeletedAt IS NOT NULL AND Completed = ? AND DueDate < ? AND DueDate >= ?");
+        assert_eq!(values(&params), vec![value(&false), value(&before), value(&after)]);
+    }
+
+    #[test]
+    fn actionable_tasks_are_open_and_not_blocked() {
+        let filter = TaskFilter {
+            scope: TaskScope::Archive,
+            actionable: true,
+            ..TaskFilter::default()
+        };
+        let (sql, params) = filter.to_sql();
+        assert_eq!(
+            sql,
+            format!(
+                " WHERE DeletedAt IS NULL AND ArchivedAt IS NOT NULL AND Completed = 0 AND NOT {}",
+                BLOCKED_CONDITION
+            )
+        );
+        assert!(params.is_empty());
+    }
+
+    #[test]
+    fn pages_are_cut_after_the_sort_with_the_id_breaking_ties() {
+        let query = TaskQuery {
+            filter: TaskFilter {
+                scope: TaskScope::Trash,
+                completed: Some(true),
+                ..TaskFilter::default()
+            },
+            sort: SortSpec::parse("priority desc, then due").unwrap(),
+            limit: Some(20),
+            offset: 40,
+        };
+        let (sql, params) = query.to_sql("Id, Title");
+        assert_eq!(
+            sql,
+            "SELECT Id, Title FROM task_item WHERE DeletedAt IS NOT NULL AND Completed = ? \
+             ORDER BY PriorityLevel DESC, DueDate ASC, Id ASC LIMIT ? OFFSET ?"
+        );
+        assert_eq!(values(&params), vec![value(&true), value(&20i64), value(&40i64)]);
+
+        let (sql, params) = TaskQuery {
+            limit: None,
+            ..query
+        }
+        .to_sql("Id");
+        assert!(sql.ends_with("ORDER BY PriorityLevel DESC, DueDate ASC, Id ASC"));
+        assert_eq!(params.len(), 1);
+    }
+}
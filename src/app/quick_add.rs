@@ -0,0 +1,147 @@
+// Quick-add syntax: a task on one line, for example "Buy milk tomorrow !2 #home @errands"
+use chrono::{DateTime, Datelike, Duration, Utc, Weekday};
+
+use crate::app::models::{parse_date, parse_tags, Priority, Task};
+
+// Words that are not part of the title:
+// "!2" or "!high" sets the priority, "#home" and "@errands" add tags,
+// "today", "tomorrow", a weekday ("friday") or a date dd.mm.yyyy sets the due date
+// Without a due date the task is due today; when a field is given twice the last one wins
+pub fn parse_quick_add(input: &str, now: DateTime<Utc>) -> Result<Task, String> {
+    let mut task = Task {
+        due_date: day_start(now),
+        ..Task::default()
+    };
+    let mut title = Vec::new();
+    let mut tags = Vec::new();
+
+    for word in input.split_whitespace() {
+        if let Some(priority) = word.strip_prefix('!') {
+            task.priority = Priority::parse(priority)
+                .ok_or(format!("Unknown priority {}, use !0 to !4 or its name", word))?;
+        } else if let Some(tag) = word.strip_prefix('#').or(word.strip_prefix('@')) {
+            if tag.is_empty() {
+                return Err(format!("{} needs a name", word));
+            }
+            tags.push(tag);
+        } else if let Some(due_date) = parse_due(word, now) {
+            task.due_date = due_date;
+        } else {
+            title.push(word);
+        }
+    }
+
+    if title.is_empty() {
+        return Err("The task needs a title".to_string());
+    }
+    task.title = title.join(" ");
+    task.tags = parse_tags(&tags.join(" "));
+    Ok(task)
+}
+
+// A weekday is its next occurrence, a week ahead when it is today
+fn parse_due(word: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let word = word.to_lowercase();
+    match word.as_str() {
+        "today" => return Some(day_start(now)),
+        "tomorrow" => return Some(day_start(now) + Duration::days(1)),
+        _ => {}
+    }
+    if let Some(date) = parse_date(&word) {
+        return Some(date);
+    }
+
+    // Only full names; short ones like "sun" or "sat" are common title words
+    if !word.ends_with("day") {
+        return None;
+    }
+    let weekday = word.parse::<Weekday>().ok()?;
+    let days_ahead = (weekday.num_days_from_monday() as i64
+        - now.weekday().num_days_from_monday() as i64)
+        .rem_euclid(7);
+    Some(day_start(now) + Duration::days(if days_ahead == 0 { 7 } else { days_ahead }))
+}
+
+fn day_start(time: DateTime<Utc>) -> DateTime<Utc> {
+    time.date_naive()
+        .and_hms_opt(0, 0, 0)
+        .map(|day| day.and_utc())
+        .unwrap_or(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // A Wednesday
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 15, 14, 30, 0).unwrap()
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_all_fields() {
+        let task = parse_quick_add("Buy milk tomorrow !2 #home @errands", now()).unwrap();
+        assert_eq!(task.title, "Buy milk");
+        assert_eq!(task.due_date, date(2024, 5, 16));
+        assert_eq!(task.priority, Priority::Medium);
+        assert_eq!(task.tags, vec!["errands", "home"]);
+        assert_eq!(task.id, None);
+        assert!(!task.completed);
+    }
+
+    #[test]
+    fn title_only_is_due_today() {
+        let task = parse_quick_add("  Call   the bank ", now()).unwrap();
+        assert_eq!(task.title, "Call the bank");
+        assert_eq!(task.due_date, date(2024, 5, 15));
+        assert_eq!(task.priority, Priority::None);
+        assert!(task.tags.is_empty());
+    }
+
+    #[test]
+    fn tokens_can_be_anywhere() {
+        let task = parse_quick_add("!urgent #Work Send report 20.05.2024", now()).unwrap();
+        assert_eq!(task.title, "Send report");
+        assert_eq!(task.due_date, date(2024, 5, 20));
+        assert_eq!(task.priority, Priority::Urgent);
+        assert_eq!(task.tags, vec!["work"]);
+    }
+
+    #[test]
+    fn weekdays_are_the_next_occurrence() {
+        let due = |input: &str| parse_quick_add(input, now()).unwrap().due_date;
+        assert_eq!(due("Gym friday"), date(2024, 5, 17));
+        assert_eq!(due("Gym Monday"), date(2024, 5, 20));
+        assert_eq!(due("Gym wednesday"), date(2024, 5, 22));
+        assert_eq!(due("Gym today"), date(2024, 5, 15));
+    }
+
+    #[test]
+    fn short_weekday_names_stay_in_the_title() {
+        let task = parse_quick_add("Buy sun cream for the sat trip", now()).unwrap();
+        assert_eq!(task.title, "Buy sun cream for the sat trip");
+        assert_eq!(task.due_date, date(2024, 5, 15));
+        assert_eq!(parse_quick_add("Gym fri", now()).unwrap().title, "Gym fri");
+    }
+
+    #[test]
+    fn repeated_fields_take_the_last_and_tags_merge() {
+        let task = parse_quick_add("Plan !1 today !3 #a #b @a tomorrow", now()).unwrap();
+        assert_eq!(task.priority, Priority::High);
+        assert_eq!(task.due_date, date(2024, 5, 16));
+        assert_eq!(task.tags, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert!(parse_quick_add("", now()).is_err());
+        assert!(parse_quick_add("tomorrow !2 #home", now()).is_err());
+        assert!(parse_quick_add("Buy milk !9", now()).is_err());
+        assert!(parse_quick_add("Buy milk #", now()).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+// Reminder scheduler of the app and the headless `remind` mode
+// A reminder fires once: whoever sees it first marks it as fired
+use chrono::Utc;
+use std::error::Error;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::app::config::RemindersConfig;
+use crate::app::models::{format_offset, DueReminder, DATE_FORMAT};
+use crate::app::storage::Storage;
+
+// How often the database is checked for due reminders unless configured otherwise
+const DEFAULT_CHECK_INTERVAL_SECONDS: u64 = 30;
+
+pub fn check_interval(config: &RemindersConfig) -> Duration {
+    Duration::from_secs(
+        config
+            .check_interval_seconds
+            .unwrap_or(DEFAULT_CHECK_INTERVAL_SECONDS)
+            .max(1),
+    )
+}
+
+// Checks for due reminders on the app tick, at most once per interval
+pub struct ReminderScheduler {
+    interval: Duration,
+    last_check: Option<Instant>,
+}
+
+impl ReminderScheduler {
+    pub fn new(interval: Duration) -> ReminderScheduler {
+        ReminderScheduler {
+            interval,
+            last_check: None,
+        }
+    }
+
+    pub fn poll(&mut self, storage: &Storage) -> Vec<DueReminder> {
+        if self
+            .last_check
+            .is_some_and(|last_check| last_check.elapsed() < self.interval)
+        {
+            return Vec::new();
+        }
+
+        self.last_check = Some(Instant::now());
+        fire_due_reminders(storage)
+    }
+}
+
+// Get the reminders whose time has come and mark them as fired
+// Reminders that could not be marked are left for the next check
+pub fn fire_due_reminders(storage: &Storage) -> Vec<DueReminder> {
+    let now = Utc::now();
+    storage
+        .get_due_reminders(now)
+        .into_iter()
+        .filter(|reminder| storage.mark_reminder_fired(reminder.reminder_id, now).is_ok_and(|marked| marked > 0))
+        .collect()
+}
+
+// One line notification, for example "Reminder: Buy milk is due 15.03.2026 (1d before)"
+pub fn describe(reminder: &DueReminder) -> String {
+    let when = if reminder.offset.is_zero() {
+        "at the due time".to_string()
+    } else {
+        format!("{} before", format_offset(reminder.offset))
+    };
+    format!(
+        "Reminder: {} is due {} ({})",
+        reminder.title,
+        reminder.due_date.format(DATE_FORMAT),
+        when
+    )
+}
+
+// Headless mode: print the due reminders or pass them to the hook command
+// Runs until killed, or checks only once with `once`
+pub fn run_daemon(
+    storage: &Storage,
+    config: &RemindersConfig,
+    once: bool,
+) -> Result<(), Box<dyn Error>> {
+    loop {
+        for reminder in fire_due_reminders(storage) {
+            match &config.hook {
+                Some(hook) => run_hook(hook, &reminder),
+                None => println!(
+                    "{} {}",
+                    Utc::now().format("%d.%m.%Y %H:%M"),
+                    describe(&reminder)
+                ),
+            }
+        }
+
+        if once {
+            return Ok(());
+        }
+        thread::sleep(check_interval(config));
+    }
+}
+
+// Run the hook with the reminder in environment variables; failures are reported, not fatal
+fn run_hook(hook: &str, reminder: &DueReminder) {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(hook)
+        .env("TASK_ID", reminder.task_id.to_string())
+        .env("TASK_TITLE", &reminder.title)
+        .env(
+            "TASK_DUE",
+            reminder.due_date.format(DATE_FORMAT).to_string(),
+        )
+        .env("REMINDER_OFFSET", format_offset(reminder.offset))
+        .env("REMINDER_MESSAGE", describe(reminder))
+        .status();
+
+    match status {
+        Ok(status) if !status.success() => eprintln!("Reminder hook failed: {}", status),
+        Err(e) => eprintln!("Failed to run the reminder hook: {}", e),
+        Ok(_) => {}
+    }
+}
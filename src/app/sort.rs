@@ -0,0 +1,174 @@
+// Sort specifications of the task list, for example "priority desc, then due asc, then title"
+use std::fmt;
+
+// Task fields the list can be sorted by
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SortedBy {
+    DueDate,
+    Name,
+    Priority,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SortKey {
+    pub by: SortedBy,
+    pub direction: SortDirection,
+}
+
+// Keys in order of importance; later keys only break ties of the earlier ones
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SortSpec {
+    pub keys: Vec<SortKey>,
+}
+
+impl SortedBy {
+    fn name(&self) -> &'static str {
+        match self {
+            SortedBy::DueDate => "due",
+            SortedBy::Name => "title",
+            SortedBy::Priority => "priority",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<SortedBy> {
+        match name {
+            "due" | "due_date" => Some(SortedBy::DueDate),
+            "title" | "name" => Some(SortedBy::Name),
+            "priority" => Some(SortedBy::Priority),
+            _ => None,
+        }
+    }
+}
+
+impl SortSpec {
+    // Parse a comma separated list of "<field> [asc|desc]", each optionally prefixed with "then"
+    pub fn parse(spec: &str) -> Result<SortSpec, String> {
+        let mut keys = Vec::new();
+        for part in spec.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let mut words = part.split_whitespace();
+            let mut field = words.next().unwrap_or_default();
+            if field == "then" {
+                field = words.next().unwrap_or_default();
+            }
+
+            let by = SortedBy::from_name(field).ok_or(format!("Unknown sort field '{}'", field))?;
+            let direction = match words.next() {
+                None | Some("asc") => SortDirection::Ascending,
+                Some("desc") => SortDirection::Descending,
+                Some(other) => return Err(format!("Unknown sort direction '{}'", other)),
+            };
+            if words.next().is_some() {
+                return Err(format!("Unexpected text in sort key '{}'", part));
+            }
+
+            keys.push(SortKey { by, direction });
+        }
+
+        Ok(SortSpec { keys })
+    }
+
+    // Make the field the most important key; toggle its direction if it already is
+    pub fn promote(&mut self, by: SortedBy) {
+        let direction = match self.keys.first() {
+            Some(first) if first.by == by => match first.direction {
+                SortDirection::Ascending => SortDirection::Descending,
+                SortDirection::Descending => SortDirection::Ascending,
+            },
+            _ => SortDirection::Ascending,
+        };
+
+        self.keys.retain(|key| key.by != by);
+        self.keys.insert(0, SortKey { by, direction });
+    }
+
+    // Short form for the List title, for example "priority ↓, due ↑"
+    pub fn summary(&self) -> String {
+        self.keys
+            .iter()
+            .map(|key| {
+                let arrow = match key.direction {
+                    SortDirection::Ascending => "↑",
+                    SortDirection::Descending => "↓",
+                };
+                format!("{} {}", key.by.name(), arrow)
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+// Written in the format accepted by SortSpec::parse
+impl fmt::Display for SortSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keys = self
+            .keys
+            .iter()
+            .map(|key| {
+                let direction = match key.direction {
+                    SortDirection::Ascending => "asc",
+                    SortDirection::Descending => "desc",
+                };
+                format!("{} {}", key.by.name(), direction)
+            })
+            .collect::<Vec<String>>();
+        write!(f, "{}", keys.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(by: SortedBy, direction: SortDirection) -> SortKey {
+        SortKey { by, direction }
+    }
+
+    #[test]
+    fn parses_keys_in_order_of_importance() {
+        let spec = SortSpec::parse("priority desc, then due asc, then title").unwrap();
+        assert_eq!(
+            spec.keys,
+            vec![
+                key(SortedBy::Priority, SortDirection::Descending),
+                key(SortedBy::DueDate, SortDirection::Ascending),
+                key(SortedBy::Name, SortDirection::Ascending),
+            ]
+        );
+    }
+
+    #[test]
+    fn accepts_field_aliases_and_skips_empty_parts() {
+        let spec = SortSpec::parse(" due_date desc,, name ,").unwrap();
+        assert_eq!(
+            spec.keys,
+            vec![
+                key(SortedBy::DueDate, SortDirection::Descending),
+                key(SortedBy::Name, SortDirection::Ascending),
+            ]
+        );
+        assert_eq!(SortSpec::parse(""), Ok(SortSpec::default()));
+    }
+
+    #[test]
+    fn rejects_unknown_fields_directions_and_trailing_text() {
+        assert_eq!(SortSpec::parse("size"), Err("Unknown sort field 'size'".to_string()));
+        assert_eq!(SortSpec::parse("then"), Err("Unknown sort field ''".to_string()));
+        assert_eq!(SortSpec::parse("due up"), Err("Unknown sort direction 'up'".to_string()));
+        assert_eq!(
+            SortSpec::parse("due asc please"),
+            Err("Unexpected text in sort key 'due asc please'".to_string())
+        );
+    }
+
+    #[test]
+    fn written_specs_parse_back() {
+        let spec = SortSpec::parse("priority desc, then due, then title desc").unwrap();
+        assert_eq!(SortSpec::parse(&spec.to_string()), Ok(spec));
+    }
+}
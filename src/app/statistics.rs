@@ -0,0 +1,128 @@
+// Statistics panel: counts, completion summary and charts
+use chrono::{Duration, Utc};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::app::models::Priority;
+
+use super::ui::App;
+
+// Number of days shown in the completion chart, today included
+const COMPLETION_CHART_DAYS: i64 = 30;
+
+// Build the UI (lines) for statistics infobox
+pub fn get_statistics_ui<'a>(app: &'a App<'a>) -> Vec<Line<'a>> {
+    let today = Utc::now().date_naive();
+    let [open, due_next_week, late] = app.items.sum_estimates();
+    let summary = app.storage.get_completion_summary();
+    let lead_time = match summary.average_lead_time {
+        Some(lead_time) if lead_time.num_hours() >= 48 => format!("{} days", lead_time.num_days()),
+        Some(lead_time) => format!("{} hours", lead_time.num_hours()),
+        None => "-".to_string(),
+    };
+    let on_time = match summary.completed {
+        0 => "-".to_string(),
+        completed => format!(
+            "{}% ({}/{})",
+            summary.on_time * 100 / completed,
+            summary.on_time,
+            completed
+        ),
+    };
+
+    let mut lines = vec![
+        Line::from(format!("Total tasks: {}", app.items.count_all())),
+        Line::from(format!("Uncompleted tasks: {}", app.items.count_uncompleted())),
+        Line::from(format!("Due in the next 7 days (late included): {}", app.items.count_due_next_week())),
+        Line::from(format!("Late: {}", app.items.count_late())),
+        Line::from(format!("Average lead time: {}", lead_time)),
+        Line::from(format!("Completed on time: {}", on_time)),
+        Line::from(format!("Estimated open: {}", open.label())),
+        Line::from(format!(
+            "Estimated in the next 7 days: {}, late: {}",
+            due_next_week.label(),
+            late.label()
+        )),
+        Line::from(format!(
+            "Pomodoros: {} today, {} in the last 7 days",
+            app.storage.count_pomodoros_since(today),
+            app.storage.count_pomodoros_since(today - Duration::days(6))
+        )),
+    ];
+    if let Some(capacity) = app.weekly_capacity.filter(|capacity| due_next_week.exceeds(*capacity)) {
+        lines.push(Line::from(Span::styled(
+            format!(
+                "Over capacity: {} due in the next 7 days, {} fit",
+                due_next_week.label(),
+                capacity.label()
+            ),
+            app.theme.error,
+        )));
+    }
+    lines
+}
+
+// Draw the statistics infobox with the completion and priority charts
+pub fn draw_statistics(f: &mut Frame, area: Rect, app: &App) {
+    let block = Block::new().title("Statistics").borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(10),
+            Constraint::Length(4),
+            Constraint::Min(0),
+        ])
+        .split(inner);
+
+    f.render_widget(
+        Paragraph::new(get_statistics_ui(app)).style(app.theme.text),
+        rows[0],
+    );
+
+    // Tasks completed per day, the oldest day on the left
+    let today = Utc::now().date_naive();
+    let first_day = today - Duration::days(COMPLETION_CHART_DAYS - 1);
+    let mut completed_per_day = vec![0; COMPLETION_CHART_DAYS as usize];
+    for (day, count) in app.storage.count_completed_per_day(first_day) {
+        if let Some(slot) = completed_per_day.get_mut((day - first_day).num_days() as usize) {
+            *slot = count;
+        }
+    }
+    let most_per_day = completed_per_day.iter().max().copied().unwrap_or(0);
+    let completion_chart = Sparkline::default()
+        .block(Block::new().title(format!(
+            "Completed per day, last {} days (max {})",
+            COMPLETION_CHART_DAYS, most_per_day
+        )))
+        .data(&completed_per_day)
+        .style(app.theme.chart);
+    f.render_widget(completion_chart, rows[1]);
+
+    // Open tasks by priority, the most important on top
+    let open_by_priority = app.storage.count_open_by_priority();
+    let bars = Priority::ALL
+        .iter()
+        .rev()
+        .map(|priority| {
+            let count = open_by_priority
+                .iter()
+                .find(|(level, _)| level == priority)
+                .map(|(_, count)| *count)
+                .unwrap_or(0);
+            Bar::default()
+                .value(count)
+                .label(Line::from(format!("{:<7}", priority.label())))
+                .style(app.theme.priority(*priority))
+                .value_style(app.theme.highlight)
+        })
+        .collect::<Vec<Bar>>();
+    let priority_chart = BarChart::default()
+        .block(Block::new().title("Open tasks by priority"))
+        .direction(Direction::Horizontal)
+        .bar_width(1)
+        .bar_gap(0)
+        .data(BarGroup::default().bars(&bars));
+    f.render_widget(priority_chart, rows[2]);
+}
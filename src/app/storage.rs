@@ -1,15 +1,221 @@
 // Communication with SQLite
 // Philosophy of CRUD lives here
 // Based on https://github.com/rusqlite/rusqlite/blob/master/examples/persons/main.rs
-use rusqlite::{Connection, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rusqlite::{
+    params_from_iter, Connection, ErrorCode, OptionalExtension, Result, Row, Transaction,
+    TransactionBehavior,
+};
 
-use crate::app::models::Task;
+use crate::app::models::{
+    format_reminders, format_tags, Attachment, CompletionSummary, DueReminder, Estimate, EstimateTotal,
+    Priority, RunningTimer, Task, TaskChange, TaskNote, TaskTemplate, TimesheetRow,
+};
+use crate::app::query::{TaskFilter, TaskQuery, TaskScope, BLOCKED_CONDITION};
+
+// Schema changes applied in order on top of the initial table
+// The number of applied migrations is kept in PRAGMA user_version
+const MIGRATIONS: &[&str] = &[
+    // Priorities 0/1/2 became levels none/medium/high of the five level scale
+    "UPDATE task_item SET PriorityLevel = CASE PriorityLevel WHEN 1 THEN 2 WHEN 2 THEN 3 ELSE 0 END;",
+    // Key-value store for UI state kept across sessions
+    "CREATE TABLE setting (Key TEXT PRIMARY KEY, Value TEXT);",
+    // Indexes for the filters and sort keys of TaskQuery
+    "CREATE INDEX idx_task_item_completed_due ON task_item (Completed, DueDate);
+     CREATE INDEX idx_task_item_due ON task_item (DueDate);
+     CREATE INDEX idx_task_item_priority ON task_item (PriorityLevel, DueDate);
+     CREATE INDEX idx_task_item_title ON task_item (Title);",
+    // Timestamps of the task lifecycle and the audit log of all changes
+    "ALTER TABLE task_item ADD COLUMN CreatedAt DATETIME;
+     ALTER TABLE task_item ADD COLUMN UpdatedAt DATETIME;
+     ALTER TABLE task_item ADD COLUMN CompletedAt DATETIME;
+     CREATE TABLE task_history (
+        Id INTEGER PRIMARY KEY AUTOINCREMENT,
+        TaskId INTEGER,
+        ChangedAt DATETIME,
+        Field TEXT,
+        OldValue TEXT,
+        NewValue TEXT
+     );
+     CREATE INDEX idx_task_history_task ON task_history (TaskId, ChangedAt);",
+    // Soft deletion and archiving of tasks
+    "ALTER TABLE task_item ADD COLUMN DeletedAt DATETIME;
+     ALTER TABLE task_item ADD COLUMN ArchivedAt DATETIME;
+     CREATE INDEX idx_task_item_scope ON task_item (DeletedAt, ArchivedAt);",
+    // Free-form tags of tasks
+    "CREATE TABLE task_tag (
+        TaskId INTEGER,
+        Tag TEXT,
+        PRIMARY KEY (TaskId, Tag)
+     );
+     CREATE INDEX idx_task_tag_tag ON task_tag (Tag);",
+    // Reminders before the due date; FiredAt is set once the reminder was shown
+    "CREATE TABLE task_reminder (
+        Id INTEGER PRIMARY KEY AUTOINCREMENT,
+        TaskId INTEGER,
+        OffsetMinutes INTEGER,
+        FiredAt DATETIME
+     );
+     CREATE INDEX idx_task_reminder_task ON task_reminder (TaskId);
+     CREATE INDEX idx_task_reminder_pending ON task_reminder (FiredAt);",
+    // Bumped on every write of the row, so writes based on an outdated read can be refused
+    "ALTER TABLE task_item ADD COLUMN Version INTEGER NOT NULL DEFAULT 0;",
+    // Tracked time; StoppedAt is NULL while the timer runs
+    "CREATE TABLE time_entry (
+        Id INTEGER PRIMARY KEY AUTOINCREMENT,
+        TaskId INTEGER,
+        StartedAt DATETIME,
+        StoppedAt DATETIME
+     );
+     CREATE INDEX idx_time_entry_task ON time_entry (TaskId);
+     CREATE INDEX idx_time_entry_started ON time_entry (StartedAt);",
+    // Finished work phases of the pomodoro timer
+    "CREATE TABLE pomodoro (
+        Id INTEGER PRIMARY KEY AUTOINCREMENT,
+        TaskId INTEGER,
+        CompletedAt DATETIME,
+        Minutes INTEGER
+     );
+     CREATE INDEX idx_pomodoro_task ON pomodoro (TaskId);
+     CREATE INDEX idx_pomodoro_completed ON pomodoro (CompletedAt);",
+    // TaskId waits until BlockedById is completed
+    "CREATE TABLE task_dependency (
+        TaskId INTEGER,
+        BlockedById INTEGER,
+        PRIMARY KEY (TaskId, BlockedById)
+     );
+     CREATE INDEX idx_task_dependency_blocker ON task_dependency (BlockedById);",
+    // At most one of them is set
+    "ALTER TABLE task_item ADD COLUMN EstimateMinutes INTEGER;
+     ALTER TABLE task_item ADD COLUMN EstimatePoints INTEGER;",
+    // Start of the day from which a deferred task is shown again
+    "ALTER TABLE task_item ADD COLUMN DeferUntil DATETIME;",
+    // Task templates and the titles of their subtasks in order
+    "CREATE TABLE task_template (
+        Id INTEGER PRIMARY KEY AUTOINCREMENT,
+        Name TEXT UNIQUE,
+        Title TEXT,
+        Description TEXT,
+        PriorityLevel INTEGER,
+        DueOffsetDays INTEGER
+     );
+     CREATE TABLE task_template_subtask (
+        TemplateId INTEGER,
+        Position INTEGER,
+        Title TEXT,
+        PRIMARY KEY (TemplateId, Position)
+     );",
+    // Append-only notes log of tasks
+    "CREATE TABLE task_note (
+        Id INTEGER PRIMARY KEY AUTOINCREMENT,
+        TaskId INTEGER,
+        CreatedAt DATETIME,
+        Text TEXT
+     );
+     CREATE INDEX idx_task_note_task ON task_note (TaskId);",
+    // Files and links attached to tasks; Target is an absolute path or a URL
+    "CREATE TABLE task_attachment (
+        Id INTEGER PRIMARY KEY AUTOINCREMENT,
+        TaskId INTEGER,
+        Target TEXT,
+        Label TEXT
+     );
+     CREATE INDEX idx_task_attachment_task ON task_attachment (TaskId);",
+];
+
+// Seconds between two stored timestamps, for SQL
+fn seconds_between(from: &str, to: &str) -> String {
+    format!(
+        "(julianday(substr({}, 1, 19)) - julianday(substr({}, 1, 19))) * 86400",
+        to, from
+    )
+}
+
+// Columns read into a Task, in the order expected by task_from_row
+const TASK_COLUMNS: &str = "Id, Title, Description, DueDate, PriorityLevel, Completed, \
+    CreatedAt, UpdatedAt, CompletedAt, DeletedAt, ArchivedAt, Version, EstimateMinutes, EstimatePoints, \
+    DeferUntil";
+
+fn task_from_row(row: &Row) -> Result<Task> {
+    Ok(Task {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        description: row.get(2)?,
+        due_date: row.get(3)?,
+        priority: row.get(4)?,
+        completed: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+        completed_at: row.get(8)?,
+        deleted_at: row.get(9)?,
+        archived_at: row.get(10)?,
+        version: row.get(11)?,
+        tags: Vec::new(),
+        reminders: Vec::new(),
+        tracked: None,
+        blocked: false,
+        latest_note: None,
+        estimate: match (row.get::<_, Option<i64>>(12)?, row.get::<_, Option<u32>>(13)?) {
+            (Some(minutes), _) => Some(Estimate::Time(Duration::minutes(minutes))),
+            (None, Some(points)) => Some(Estimate::Points(points)),
+            (None, None) => None,
+        },
+        defer_until: row.get(14)?,
+    })
+}
+
+// Values of the EstimateMinutes and EstimatePoints columns
+fn estimate_columns(estimate: Option<Estimate>) -> (Option<i64>, Option<u32>) {
+    match estimate {
+        Some(Estimate::Time(time)) => (Some(time.num_minutes()), None),
+        Some(Estimate::Points(points)) => (None, Some(points)),
+        None => (None, None),
+    }
+}
+
+// Fields of the task as written to the history, in the order they are compared
+fn history_values(task: &Task) -> [(&'static str, String); 9] {
+    [
+        ("title", task.title.clone()),
+        ("description", task.description.clone()),
+        ("due date", task.due_date.format("%d.%m.%Y").to_string()),
+        ("priority", task.priority.label().to_string()),
+        ("completed", if task.completed { "yes" } else { "no" }.to_string()),
+        ("tags", format_tags(&task.tags)),
+        ("reminders", format_reminders(&task.reminders)),
+        ("estimate", task.estimate.map(|estimate| estimate.label()).unwrap_or_default()),
+        (
+            "start date",
+            task.defer_until
+                .map(|date| date.format("%d.%m.%Y").to_string())
+                .unwrap_or_default(),
+        ),
+    ]
+}
 
 pub struct Storage {
     pub db_con: Connection,
 }
 
 impl Storage {
+    // Several instances may share the database: with WAL readers do not block the writer,
+    // and a writer waits for another one instead of failing right away
+    pub fn enable_sharing(&self) {
+        self.db_con
+            .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))
+            .expect("Could not switch the DB to WAL mode");
+        self.db_con
+            .busy_timeout(std::time::Duration::from_secs(5))
+            .expect("Could not set the DB busy timeout");
+    }
+
+    // Takes the write lock up front, so a read inside the transaction cannot go stale:
+    // a deferred one fails right away when another instance wrote since its first read,
+    // while this one waits for the busy timeout
+    fn write_transaction(&self) -> Result<Transaction<'_>> {
+        Transaction::new_unchecked(&self.db_con, TransactionBehavior::Immediate)
+    }
+
     pub fn create_table_if_not_exists(&self) {
         self.db_con
             .execute(
@@ -24,52 +230,1020 @@ impl Storage {
                 (),
             )
             .expect("Could not create the initial DB table");
+        self.migrate();
+    }
+
+    // Apply the migrations the database has not seen yet
+    // Each step reads the version again under the write lock, so two instances starting at once
+    // cannot both apply the same migration
+    fn migrate(&self) {
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(self.schema_version()) {
+            let transaction = self.write_transaction().expect("Could not migrate the DB schema");
+            if self.schema_version() > i {
+                continue;
+            }
+            self.db_con
+                .execute_batch(&format!("{} PRAGMA user_version = {};", migration, i + 1))
+                .and_then(|_| transaction.commit())
+                .expect("Could not migrate the DB schema");
+        }
+    }
+
+    fn schema_version(&self) -> usize {
+        self.db_con
+            .query_row("PRAGMA user_version;", [], |row| row.get(0))
+            .expect("Could not read the DB schema version")
     }
 
     // CREATE
-    pub fn insert_task(&self, task: &Task) -> Result<usize> {
-        return self.db_con.execute(
-            "INSERT INTO task_item (Title, Description, DueDate, PriorityLevel, Completed) VALUES (?1, ?2, ?3, ?4, ?5);",
-            (&task.title, &task.description, &task.due_date, &task.priority, &task.completed),
-        );
+    // Returns the id of the new task
+    pub fn insert_task(&self, task: &Task) -> Result<i32> {
+        let transaction = self.write_transaction()?;
+        let task_id = self.write_new_task(task)?;
+        transaction.commit()?;
+        Ok(task_id)
+    }
+
+    // Insert the task and its subtasks, and make the task wait for them, all or nothing
+    pub fn insert_task_with_subtasks(&self, task: &Task, subtasks: &[Task]) -> Result<i32> {
+        let transaction = self.write_transaction()?;
+        let task_id = self.write_new_task(task)?;
+        let subtask_ids = subtasks
+            .iter()
+            .map(|subtask| self.write_new_task(subtask))
+            .collect::<Result<Vec<i32>>>()?;
+        self.write_dependencies(task_id, &subtask_ids)?;
+        transaction.commit()?;
+        Ok(task_id)
+    }
+
+    // Part of the caller's transaction
+    fn write_new_task(&self, task: &Task) -> Result<i32> {
+        let now = Utc::now();
+        let completed_at = if task.completed { Some(now) } else { None };
+        let (estimate_minutes, estimate_points) = estimate_columns(task.estimate);
+        self.db_con.execute(
+            "INSERT INTO task_item (Title, Description, DueDate, PriorityLevel, Completed, CreatedAt, UpdatedAt, CompletedAt, EstimateMinutes, EstimatePoints, DeferUntil) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, ?7, ?8, ?9, ?10);",
+            (&task.title, &task.description, &task.due_date, &task.priority, &task.completed, now, completed_at, estimate_minutes, estimate_points, task.defer_until),
+        )?;
+        let task_id = self.db_con.last_insert_rowid();
+        self.write_tags(task_id, &task.tags)?;
+        self.write_reminders(task_id, &task.reminders)?;
+        self.add_history(task_id, "created", None, Some(&task.title))?;
+        Ok(task_id as i32)
     }
 
     // READ
-    pub fn get_all_tasks(&self) -> Vec<Task> {
+    // Tasks matching the query's filter, in its order and page
+    pub fn query_tasks(&self, query: &TaskQuery) -> Vec<Task> {
+        let (sql, params) = query.to_sql(TASK_COLUMNS);
         let mut stmt = self
             .db_con
-            .prepare("SELECT * FROM task_item")
+            .prepare(&sql)
             .expect("Failed to prepare for task retrieval");
 
-        let results = stmt.query_map([], |row| {
-            Ok(Task {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                description: row.get(2)?,
-                due_date: row.get(3)?,
-                priority: row.get(4)?,
-                completed: row.get(5)?,
-            })
-        });
+        let results = stmt.query_map(params_from_iter(params.iter()), task_from_row);
 
-        return match results {
+        let mut tasks: Vec<Task> = match results {
             Ok(tasks) => tasks.filter_map(|task_result| task_result.ok()).collect(),
             Err(_) => Vec::new(),
         };
+        self.read_related(&mut tasks);
+        tasks
+    }
+
+    // Number of tasks matching the filter
+    pub fn count_tasks(&self, filter: &TaskFilter) -> usize {
+        let (where_clause, params) = filter.to_sql();
+        self.db_con
+            .query_row(
+                &format!("SELECT COUNT(*) FROM task_item{}", where_clause),
+                params_from_iter(params.iter()),
+                |row| row.get(0),
+            )
+            .unwrap_or(0)
+    }
+
+    // Task with the given id
+    pub fn get_task(&self, task_id: i32) -> Option<Task> {
+        let task = self
+            .db_con
+            .query_row(
+                &format!("SELECT {} FROM task_item WHERE Id = ?;", TASK_COLUMNS),
+                [task_id],
+                task_from_row,
+            )
+            .optional()
+            .unwrap_or(None)?;
+
+        let mut tasks = vec![task];
+        self.read_related(&mut tasks);
+        tasks.pop()
+    }
+
+    // Fill in the tags, reminders, tracked time and blocked state of the tasks, with one query for each
+    fn read_related(&self, tasks: &mut [Task]) {
+        let task_ids = tasks.iter().filter_map(|task| task.id).collect::<Vec<i32>>();
+        if task_ids.is_empty() {
+            return;
+        }
+
+        let placeholders = vec!["?"; task_ids.len()].join(", ");
+        self.read_tags(tasks, &task_ids, &placeholders);
+        self.read_reminders(tasks, &task_ids, &placeholders);
+        self.read_tracked(tasks, &task_ids, &placeholders);
+        self.read_blocked(tasks, &task_ids, &placeholders);
+        self.read_latest_notes(tasks, &task_ids, &placeholders);
+    }
+
+    fn read_tags(&self, tasks: &mut [Task], task_ids: &[i32], placeholders: &str) {
+        let mut stmt = self
+            .db_con
+            .prepare(&format!(
+                "SELECT TaskId, Tag FROM task_tag WHERE TaskId IN ({}) ORDER BY Tag;",
+                placeholders
+            ))
+            .expect("Failed to prepare for tag retrieval");
+
+        let results = stmt.query_map(params_from_iter(task_ids.iter()), |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?))
+        });
+
+        if let Ok(tags) = results {
+            for (task_id, tag) in tags.filter_map(|tag| tag.ok()) {
+                if let Some(task) = tasks.iter_mut().find(|task| task.id == Some(task_id)) {
+                    task.tags.push(tag);
+                }
+            }
+        }
+    }
+
+    fn read_reminders(&self, tasks: &mut [Task], task_ids: &[i32], placeholders: &str) {
+        let mut stmt = self
+            .db_con
+            .prepare(&format!(
+                "SELECT TaskId, OffsetMinutes FROM task_reminder WHERE TaskId IN ({}) ORDER BY OffsetMinutes DESC;",
+                placeholders
+            ))
+            .expect("Failed to prepare for reminder retrieval");
+
+        let results = stmt.query_map(params_from_iter(task_ids.iter()), |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, i64>(1)?))
+        });
+
+        if let Ok(reminders) = results {
+            for (task_id, minutes) in reminders.filter_map(|reminder| reminder.ok()) {
+                if let Some(task) = tasks.iter_mut().find(|task| task.id == Some(task_id)) {
+                    task.reminders.push(Duration::minutes(minutes));
+                }
+            }
+        }
+    }
+
+    fn read_tracked(&self, tasks: &mut [Task], task_ids: &[i32], placeholders: &str) {
+        let mut stmt = self
+            .db_con
+            .prepare(&format!(
+                "SELECT TaskId, SUM({}) FROM time_entry WHERE StoppedAt IS NOT NULL AND TaskId IN ({}) GROUP BY TaskId;",
+                seconds_between("StartedAt", "StoppedAt"),
+                placeholders
+            ))
+            .expect("Failed to prepare for tracked time retrieval");
+
+        let results = stmt.query_map(params_from_iter(task_ids.iter()), |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, f64>(1)?))
+        });
+
+        if let Ok(totals) = results {
+            for (task_id, seconds) in totals.filter_map(|total| total.ok()) {
+                if let Some(task) = tasks.iter_mut().find(|task| task.id == Some(task_id)) {
+                    task.tracked = Some(Duration::seconds(seconds.round() as i64));
+                }
+            }
+        }
+    }
+
+    fn read_blocked(&self, tasks: &mut [Task], task_ids: &[i32], placeholders: &str) {
+        let mut stmt = self
+            .db_con
+            .prepare(&format!(
+                "SELECT Id FROM task_item WHERE Id IN ({}) AND {};",
+                placeholders, BLOCKED_CONDITION
+            ))
+            .expect("Failed to prepare for blocked task retrieval");
+
+        let results = stmt.query_map(params_from_iter(task_ids.iter()), |row| row.get::<_, i32>(0));
+
+        if let Ok(blocked_ids) = results {
+            for task_id in blocked_ids.filter_map(|task_id| task_id.ok()) {
+                if let Some(task) = tasks.iter_mut().find(|task| task.id == Some(task_id)) {
+                    task.blocked = true;
+                }
+            }
+        }
+    }
+
+    fn read_latest_notes(&self, tasks: &mut [Task], task_ids: &[i32], placeholders: &str) {
+        let mut stmt = self
+            .db_con
+            .prepare(&format!(
+                "SELECT TaskId, CreatedAt, Text FROM task_note WHERE Id IN (SELECT MAX(Id) FROM task_note WHERE TaskId IN ({}) GROUP BY TaskId);",
+                placeholders
+            ))
+            .expect("Failed to prepare for note retrieval");
+
+        let results = stmt.query_map(params_from_iter(task_ids.iter()), |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                TaskNote {
+                    created_at: row.get(1)?,
+                    text: row.get(2)?,
+                },
+            ))
+        });
+
+        if let Ok(notes) = results {
+            for (task_id, note) in notes.filter_map(|note| note.ok()) {
+                if let Some(task) = tasks.iter_mut().find(|task| task.id == Some(task_id)) {
+                    task.latest_note = Some(note);
+                }
+            }
+        }
+    }
+
+    // Changes of the task, the newest first
+    pub fn get_task_history(&self, task_id: i32) -> Vec<TaskChange> {
+        let mut stmt = self
+            .db_con
+            .prepare("SELECT ChangedAt, Field, OldValue, NewValue FROM task_history WHERE TaskId = ? ORDER BY ChangedAt DESC, Id DESC;")
+            .expect("Failed to prepare for history retrieval");
+
+        let results = stmt.query_map([task_id], |row| {
+            Ok(TaskChange {
+                changed_at: row.get(0)?,
+                field: row.get(1)?,
+                old_value: row.get(2)?,
+                new_value: row.get(3)?,
+            })
+        });
+
+        match results {
+            Ok(changes) => changes.filter_map(|change| change.ok()).collect(),
+            Err(_) => Vec::new(),
+        }
     }
 
     // UPDATE
+    // Every changed field is appended to the task history
+    // Nothing is written and 0 is returned if the task is gone or was changed since it was read
     pub fn update_task(&self, task: &Task) -> Result<usize> {
-        return self.db_con.execute(
-            "UPDATE task_item SET Title = ?, Description = ?, DueDate = ?, PriorityLevel = ?, Completed = ? WHERE Id = ?;",
-            (&task.title, &task.description, &task.due_date, &task.priority, &task.completed, &task.id),
-        );
+        let transaction = self.write_transaction()?;
+        let updated = self.write_task(task)?;
+        transaction.commit()?;
+        Ok(updated)
+    }
+
+    // Apply the same change to all the given tasks in one transaction
+    pub fn update_tasks(&self, task_ids: &[i32], change: impl Fn(&mut Task)) -> Result<usize> {
+        let transaction = self.write_transaction()?;
+        let mut updated = 0;
+        for task_id in task_ids {
+            if let Some(mut task) = self.get_task(*task_id) {
+                change(&mut task);
+                updated += self.write_task(&task)?;
+            }
+        }
+        transaction.commit()?;
+        Ok(updated)
+    }
+
+    // Write the task over its stored version; the caller owns the transaction
+    fn write_task(&self, task: &Task) -> Result<usize> {
+        let task_id = task.id.unwrap_or(-1);
+        let old_task = match self.get_task(task_id) {
+            Some(old_task) if old_task.version == task.version => old_task,
+            _ => return Ok(0),
+        };
+
+        let now = Utc::now();
+        let completed_at = match (old_task.completed, task.completed) {
+            (false, true) => Some(now),
+            (true, true) => old_task.completed_at,
+            _ => None,
+        };
+        let (estimate_minutes, estimate_points) = estimate_columns(task.estimate);
+
+        let updated = self.db_con.execute(
+            "UPDATE task_item SET Title = ?, Description = ?, DueDate = ?, PriorityLevel = ?, Completed = ?, UpdatedAt = ?, CompletedAt = ?, EstimateMinutes = ?, EstimatePoints = ?, DeferUntil = ?, Version = Version + 1 WHERE Id = ? AND Version = ?;",
+            (&task.title, &task.description, &task.due_date, &task.priority, &task.completed, now, completed_at, estimate_minutes, estimate_points, task.defer_until, task_id, task.version),
+        )?;
+        if updated == 0 {
+            return Ok(0);
+        }
+        if old_task.tags != task.tags {
+            self.write_tags(task_id as i64, &task.tags)?;
+        }
+        // Reminders fire again for the new due date
+        if old_task.reminders != task.reminders || old_task.due_date != task.due_date {
+            self.write_reminders(task_id as i64, &task.reminders)?;
+        }
+
+        for ((field, old_value), (_, new_value)) in history_values(&old_task)
+            .into_iter()
+            .zip(history_values(task))
+        {
+            if old_value != new_value {
+                self.add_history(task_id as i64, field, Some(&old_value), Some(&new_value))?;
+            }
+        }
+
+        Ok(updated)
+    }
+
+    // Replace the tags of the task
+    fn write_tags(&self, task_id: i64, tags: &[String]) -> Result<()> {
+        self.db_con
+            .execute("DELETE FROM task_tag WHERE TaskId = ?;", [task_id])?;
+        for tag in tags {
+            self.db_con.execute(
+                "INSERT OR IGNORE INTO task_tag (TaskId, Tag) VALUES (?1, ?2);",
+                (task_id, tag),
+            )?;
+        }
+        Ok(())
+    }
+
+    // Replace the reminders of the task; none of them has fired yet
+    fn write_reminders(&self, task_id: i64, reminders: &[Duration]) -> Result<()> {
+        self.db_con
+            .execute("DELETE FROM task_reminder WHERE TaskId = ?;", [task_id])?;
+        for offset in reminders {
+            self.db_con.execute(
+                "INSERT INTO task_reminder (TaskId, OffsetMinutes) VALUES (?1, ?2);",
+                (task_id, offset.num_minutes()),
+            )?;
+        }
+        Ok(())
+    }
+
+    // Append a change to the task history
+    fn add_history(
+        &self,
+        task_id: i64,
+        field: &str,
+        old_value: Option<&str>,
+        new_value: Option<&str>,
+    ) -> Result<usize> {
+        self.db_con.execute(
+            "INSERT INTO task_history (TaskId, ChangedAt, Field, OldValue, NewValue) VALUES (?1, ?2, ?3, ?4, ?5);",
+            (task_id, Utc::now(), field, old_value, new_value),
+        )
     }
 
     // DELETE
-    pub fn delete_task(&self, task_id: i32) -> Result<usize> {
-        return self
+    // Tasks are moved to the trash first
+    pub fn delete_tasks(&self, task_ids: &[i32]) -> Result<usize> {
+        self.set_lifecycle_time(task_ids, "DeletedAt", Some(Utc::now()), "deleted")
+    }
+
+    // Take the tasks out of the trash
+    pub fn restore_tasks(&self, task_ids: &[i32]) -> Result<usize> {
+        self.set_lifecycle_time(task_ids, "DeletedAt", None, "restored")
+    }
+
+    // Take the tasks out of the archive
+    pub fn unarchive_tasks(&self, task_ids: &[i32]) -> Result<usize> {
+        self.set_lifecycle_time(task_ids, "ArchivedAt", None, "unarchived")
+    }
+
+    // Delete the tasks for good, together with their tags, reminders, tracked time, pomodoros,
+    // dependencies and history
+    pub fn purge_tasks(&self, task_ids: &[i32]) -> Result<usize> {
+        let transaction = self.write_transaction()?;
+        let mut purged = 0;
+        for task_id in task_ids {
+            purged += transaction.execute("DELETE FROM task_item WHERE Id = ?;", [task_id])?;
+            transaction.execute("DELETE FROM task_tag WHERE TaskId = ?;", [task_id])?;
+            transaction.execute("DELETE FROM task_reminder WHERE TaskId = ?;", [task_id])?;
+            transaction.execute("DELETE FROM time_entry WHERE TaskId = ?;", [task_id])?;
+            transaction.execute("DELETE FROM pomodoro WHERE TaskId = ?;", [task_id])?;
+            transaction.execute("DELETE FROM task_note WHERE TaskId = ?;", [task_id])?;
+            transaction.execute("DELETE FROM task_attachment WHERE TaskId = ?;", [task_id])?;
+            transaction.execute(
+                "DELETE FROM task_dependency WHERE TaskId = ?1 OR BlockedById = ?1;",
+                [task_id],
+            )?;
+            transaction.execute("DELETE FROM task_history WHERE TaskId = ?;", [task_id])?;
+        }
+        transaction.commit()?;
+        Ok(purged)
+    }
+
+    // Move the active tasks completed before the given time to the archive
+    pub fn archive_completed_before(&self, before: DateTime<Utc>) -> Result<usize> {
+        let now = Utc::now();
+        let condition = format!(
+            "{} AND Completed = 1 AND CompletedAt < ?1",
+            TaskScope::Active.condition()
+        );
+        let transaction = self.write_transaction()?;
+        transaction.execute(
+            &format!("INSERT INTO task_history (TaskId, ChangedAt, Field) SELECT Id, ?2, 'archived' FROM task_item WHERE {};", condition),
+            (before, now),
+        )?;
+        let archived = transaction.execute(
+            &format!("UPDATE task_item SET ArchivedAt = ?2, Version = Version + 1 WHERE {};", condition),
+            (before, now),
+        )?;
+        transaction.commit()?;
+        Ok(archived)
+    }
+
+    // Set or clear a lifecycle timestamp column of the tasks and note it in their history
+    fn set_lifecycle_time(
+        &self,
+        task_ids: &[i32],
+        column: &str,
+        time: Option<DateTime<Utc>>,
+        change: &str,
+    ) -> Result<usize> {
+        let transaction = self.write_transaction()?;
+        let mut updated = 0;
+        for task_id in task_ids {
+            let updated_task = transaction.execute(
+                &format!("UPDATE task_item SET {} = ?, Version = Version + 1 WHERE Id = ?;", column),
+                (time, task_id),
+            )?;
+            if updated_task > 0 {
+                self.add_history(*task_id as i64, change, None, None)?;
+            }
+            updated += updated_task;
+        }
+        transaction.commit()?;
+        Ok(updated)
+    }
+
+    // DEPENDENCIES
+    // Make the task wait for the blockers
+    // Refused as a whole if a blocker is the task itself or already waits for it, directly or not
+    pub fn add_dependencies(&self, task_id: i32, blocker_ids: &[i32]) -> Result<usize, String> {
+        // The check and the write see the same links, even with other instances writing
+        let transaction = self.write_transaction().map_err(write_error)?;
+        for blocker_id in blocker_ids {
+            if self.depends_on(*blocker_id, task_id) {
+                let title = |task_id| self.get_task(task_id).map(|task| task.title).unwrap_or_default();
+                return Err(if *blocker_id == task_id {
+                    format!("{} cannot wait for itself", title(task_id))
+                } else {
+                    format!("{} already waits for {}", title(*blocker_id), title(task_id))
+                });
+            }
+        }
+
+        let added = self.write_dependencies(task_id, blocker_ids).map_err(write_error)?;
+        transaction.commit().map_err(write_error)?;
+        Ok(added)
+    }
+
+    // Part of the caller's transaction
+    fn write_dependencies(&self, task_id: i32, blocker_ids: &[i32]) -> Result<usize> {
+        let mut added = 0;
+        for blocker_id in blocker_ids {
+            let added_dependency = self.db_con.execute(
+                "INSERT OR IGNORE INTO task_dependency (TaskId, BlockedById) VALUES (?1, ?2);",
+                (task_id, blocker_id),
+            )?;
+            if added_dependency > 0 {
+                let blocker = self.get_task(*blocker_id).map(|task| task.title);
+                self.add_history(task_id as i64, "blocked by", None, blocker.as_deref())?;
+            }
+            added += added_dependency;
+        }
+        Ok(added)
+    }
+
+    // Whether the task waits for the other one, directly or through other tasks
+    // A task is considered to depend on itself
+    fn depends_on(&self, task_id: i32, other_id: i32) -> bool {
+        task_id == other_id
+            || self
+                .db_con
+                .query_row(
+                    "WITH RECURSIVE blocker (Id) AS (
+                        SELECT BlockedById FROM task_dependency WHERE TaskId = ?1
+                        UNION
+                        SELECT BlockedById FROM task_dependency JOIN blocker ON TaskId = blocker.Id
+                    )
+                    SELECT EXISTS (SELECT 1 FROM blocker WHERE Id = ?2);",
+                    (task_id, other_id),
+                    |row| row.get(0),
+                )
+                .unwrap_or(false)
+    }
+
+    // Stop the task from waiting for any other task
+    pub fn remove_dependencies(&self, task_id: i32) -> Result<usize> {
+        let transaction = self.write_transaction()?;
+        let removed =
+            transaction.execute("DELETE FROM task_dependency WHERE TaskId = ?;", [task_id])?;
+        if removed > 0 {
+            self.add_history(task_id as i64, "dependencies removed", None, None)?;
+        }
+        transaction.commit()?;
+        Ok(removed)
+    }
+
+    // Tasks the given one waits for
+    pub fn get_blockers(&self, task_id: i32) -> Vec<Task> {
+        self.get_linked_tasks(
+            "Id IN (SELECT BlockedById FROM task_dependency WHERE TaskId = ?)",
+            task_id,
+        )
+    }
+
+    // Tasks waiting for the given one
+    pub fn get_dependents(&self, task_id: i32) -> Vec<Task> {
+        self.get_linked_tasks(
+            "Id IN (SELECT TaskId FROM task_dependency WHERE BlockedById = ?)",
+            task_id,
+        )
+    }
+
+    fn get_linked_tasks(&self, condition: &str, task_id: i32) -> Vec<Task> {
+        let mut stmt = self
             .db_con
-            .execute("DELETE FROM task_item WHERE Id = ?;", [task_id]);
+            .prepare(&format!(
+                "SELECT {} FROM task_item WHERE {} AND DeletedAt IS NULL ORDER BY Title;",
+                TASK_COLUMNS, condition
+            ))
+            .expect("Failed to prepare for linked task retrieval");
+
+        let mut tasks: Vec<Task> = match stmt.query_map([task_id], task_from_row) {
+            Ok(tasks) => tasks.filter_map(|task_result| task_result.ok()).collect(),
+            Err(_) => Vec::new(),
+        };
+        self.read_related(&mut tasks);
+        tasks
+    }
+
+    // REMINDERS
+    // Reminders not shown yet whose time has come, of the active uncompleted tasks
+    pub fn get_due_reminders(&self, now: DateTime<Utc>) -> Vec<DueReminder> {
+        let mut stmt = self
+            .db_con
+            .prepare(&format!(
+                "SELECT task_reminder.Id, OffsetMinutes, task_item.Id, Title, DueDate
+                FROM task_reminder JOIN task_item ON task_item.Id = task_reminder.TaskId
+                WHERE FiredAt IS NULL AND {} AND Completed = 0
+                    AND julianday(substr(DueDate, 1, 19)) - OffsetMinutes / 1440.0 <= julianday(?)
+                ORDER BY DueDate;",
+                TaskScope::Active.condition()
+            ))
+            .expect("Failed to prepare for reminder retrieval");
+
+        let results = stmt.query_map([now.format("%Y-%m-%d %H:%M:%S").to_string()], |row| {
+            Ok(DueReminder {
+                reminder_id: row.get(0)?,
+                offset: Duration::minutes(row.get(1)?),
+                task_id: row.get(2)?,
+                title: row.get(3)?,
+                due_date: row.get(4)?,
+            })
+        });
+
+        match results {
+            Ok(reminders) => reminders.filter_map(|reminder| reminder.ok()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // Returns 0 if another instance has fired the reminder meanwhile
+    pub fn mark_reminder_fired(&self, reminder_id: i64, fired_at: DateTime<Utc>) -> Result<usize> {
+        self.db_con.execute(
+            "UPDATE task_reminder SET FiredAt = ?1 WHERE Id = ?2 AND FiredAt IS NULL;",
+            (fired_at, reminder_id),
+        )
+    }
+
+    // TIME TRACKING
+    // Start a timer on the task, stopping the one running on any task
+    pub fn start_timer(&self, task_id: i32, now: DateTime<Utc>) -> Result<()> {
+        let transaction = self.write_transaction()?;
+        transaction.execute("UPDATE time_entry SET StoppedAt = ? WHERE StoppedAt IS NULL;", [now])?;
+        transaction.execute(
+            "INSERT INTO time_entry (TaskId, StartedAt) VALUES (?1, ?2);",
+            (task_id, now),
+        )?;
+        transaction.commit()
+    }
+
+    pub fn stop_timer(&self, now: DateTime<Utc>) -> Result<usize> {
+        self.db_con
+            .execute("UPDATE time_entry SET StoppedAt = ? WHERE StoppedAt IS NULL;", [now])
+    }
+
+    pub fn get_running_timer(&self) -> Option<RunningTimer> {
+        self.db_con
+            .query_row(
+                "SELECT TaskId, Title, StartedAt FROM time_entry
+                JOIN task_item ON task_item.Id = time_entry.TaskId
+                WHERE StoppedAt IS NULL ORDER BY StartedAt DESC LIMIT 1;",
+                [],
+                |row| {
+                    Ok(RunningTimer {
+                        task_id: row.get(0)?,
+                        title: row.get(1)?,
+                        started_at: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+            .unwrap_or(None)
+    }
+
+    // Time tracked per day and task between the given days, both included
+    // An entry counts on the day it was started; the running one counts until now
+    // Tasks in the trash and archive are included
+    pub fn get_timesheet(
+        &self,
+        since: NaiveDate,
+        until: NaiveDate,
+        now: DateTime<Utc>,
+    ) -> Vec<TimesheetRow> {
+        let mut stmt = self
+            .db_con
+            .prepare(&format!(
+                "SELECT substr(StartedAt, 1, 10) AS Day, TaskId, Title, SUM({})
+                FROM time_entry JOIN task_item ON task_item.Id = time_entry.TaskId
+                WHERE Day >= ?2 AND Day <= ?3
+                GROUP BY Day, TaskId ORDER BY Day, Title;",
+                seconds_between("StartedAt", "COALESCE(StoppedAt, ?1)")
+            ))
+            .expect("Failed to prepare for timesheet retrieval");
+
+        let results = stmt.query_map(
+            (
+                now,
+                since.format("%Y-%m-%d").to_string(),
+                until.format("%Y-%m-%d").to_string(),
+            ),
+            |row| {
+                Ok(TimesheetRow {
+                    day: row.get(0)?,
+                    task_id: row.get(1)?,
+                    title: row.get(2)?,
+                    tracked: Duration::seconds(row.get::<_, f64>(3)?.round() as i64),
+                })
+            },
+        );
+
+        match results {
+            Ok(rows) => rows.filter_map(|row| row.ok()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // POMODOROS
+    pub fn add_pomodoro(&self, task_id: i32, completed_at: DateTime<Utc>, minutes: i64) -> Result<usize> {
+        self.db_con.execute(
+            "INSERT INTO pomodoro (TaskId, CompletedAt, Minutes) VALUES (?1, ?2, ?3);",
+            (task_id, completed_at, minutes),
+        )
+    }
+
+    pub fn count_task_pomodoros(&self, task_id: i32) -> u64 {
+        self.db_con
+            .query_row("SELECT COUNT(*) FROM pomodoro WHERE TaskId = ?;", [task_id], |row| {
+                row.get(0)
+            })
+            .unwrap_or(0)
+    }
+
+    // NOTES
+    pub fn add_note(&self, task_id: i32, text: &str, created_at: DateTime<Utc>) -> Result<usize> {
+        self.db_con.execute(
+            "INSERT INTO task_note (TaskId, CreatedAt, Text) VALUES (?1, ?2, ?3);",
+            (task_id, created_at, text),
+        )
+    }
+
+    // Notes of the task, the newest first
+    pub fn get_notes(&self, task_id: i32) -> Vec<TaskNote> {
+        let mut stmt = self
+            .db_con
+            .prepare("SELECT CreatedAt, Text FROM task_note WHERE TaskId = ? ORDER BY Id DESC;")
+            .expect("Failed to prepare for note retrieval");
+
+        let results = stmt.query_map([task_id], |row| {
+            Ok(TaskNote {
+                created_at: row.get(0)?,
+                text: row.get(1)?,
+            })
+        });
+        match results {
+            Ok(notes) => notes.filter_map(|note| note.ok()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // ATTACHMENTS
+    pub fn add_attachment(&self, task_id: i32, attachment: &Attachment) -> Result<()> {
+        let transaction = self.write_transaction()?;
+        transaction.execute(
+            "INSERT INTO task_attachment (TaskId, Target, Label) VALUES (?1, ?2, ?3);",
+            (task_id, &attachment.target, &attachment.label),
+        )?;
+        self.add_history(task_id as i64, "attached", None, Some(&attachment.label))?;
+        transaction.commit()
+    }
+
+    // In the order they were attached
+    pub fn get_attachments(&self, task_id: i32) -> Vec<Attachment> {
+        let mut stmt = self
+            .db_con
+            .prepare("SELECT Id, Target, Label FROM task_attachment WHERE TaskId = ? ORDER BY Id;")
+            .expect("Failed to prepare for attachment retrieval");
+
+        let results = stmt.query_map([task_id], |row| {
+            Ok(Attachment {
+                id: row.get(0)?,
+                target: row.get(1)?,
+                label: row.get(2)?,
+            })
+        });
+        match results {
+            Ok(attachments) => attachments.filter_map(|attachment| attachment.ok()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub fn remove_attachment(&self, task_id: i32, attachment: &Attachment) -> Result<()> {
+        let transaction = self.write_transaction()?;
+        transaction.execute("DELETE FROM task_attachment WHERE Id = ?;", [attachment.id])?;
+        self.add_history(task_id as i64, "detached", Some(&attachment.label), None)?;
+        transaction.commit()
+    }
+
+    // STATISTICS
+    // Timestamps are stored in UTC as "YYYY-MM-DD HH:MM:SS...", so their prefixes are the day and second
+    // Only the active tasks are counted
+
+    // Number of tasks completed on each day since the given one; days without completions are missing
+    pub fn count_completed_per_day(&self, since: NaiveDate) -> Vec<(NaiveDate, u64)> {
+        let mut stmt = self
+            .db_con
+            .prepare(&format!(
+                "SELECT substr(CompletedAt, 1, 10) AS Day, COUNT(*) FROM task_item WHERE {} AND Completed = 1 AND Day >= ? GROUP BY Day;",
+                TaskScope::Active.condition()
+            ))
+            .expect("Failed to prepare for statistics retrieval");
+
+        let results = stmt.query_map([since.format("%Y-%m-%d").to_string()], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        });
+
+        match results {
+            Ok(days) => days.filter_map(|day| day.ok()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // Number of uncompleted tasks of each priority; priorities without tasks are missing
+    pub fn count_open_by_priority(&self) -> Vec<(Priority, u64)> {
+        let mut stmt = self
+            .db_con
+            .prepare(&format!(
+                "SELECT PriorityLevel, COUNT(*) FROM task_item WHERE {} AND Completed = 0 GROUP BY PriorityLevel;",
+                TaskScope::Active.condition()
+            ))
+            .expect("Failed to prepare for statistics retrieval");
+
+        let results = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)));
+
+        match results {
+            Ok(priorities) => priorities.filter_map(|priority| priority.ok()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // Pomodoros finished since the start of the given day, of any task
+    pub fn count_pomodoros_since(&self, since: NaiveDate) -> u64 {
+        self.db_con
+            .query_row(
+                "SELECT COUNT(*) FROM pomodoro WHERE substr(CompletedAt, 1, 10) >= ?;",
+                [since.format("%Y-%m-%d").to_string()],
+                |row| row.get(0),
+            )
+            .unwrap_or(0)
+    }
+
+    // Estimates of the tasks matching the filter, added up
+    pub fn sum_estimates(&self, filter: &TaskFilter) -> EstimateTotal {
+        let (where_clause, params) = filter.to_sql();
+        self.db_con
+            .query_row(
+                &format!(
+                    "SELECT COALESCE(SUM(EstimateMinutes), 0), COALESCE(SUM(EstimatePoints), 0) FROM task_item{}",
+                    where_clause
+                ),
+                params_from_iter(params.iter()),
+                |row| {
+                    Ok(EstimateTotal {
+                        time: Duration::minutes(row.get(0)?),
+                        points: row.get(1)?,
+                    })
+                },
+            )
+            .unwrap_or(EstimateTotal {
+                time: Duration::zero(),
+                points: 0,
+            })
+    }
+
+    pub fn get_completion_summary(&self) -> CompletionSummary {
+        self.db_con
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*),
+                        SUM(substr(CompletedAt, 1, 10) <= substr(DueDate, 1, 10)),
+                        AVG(julianday(substr(CompletedAt, 1, 19)) - julianday(substr(CreatedAt, 1, 19)))
+                    FROM task_item WHERE {} AND Completed = 1 AND CompletedAt IS NOT NULL;",
+                    TaskScope::Active.condition()
+                ),
+                [],
+                |row| {
+                    let average_days: Option<f64> = row.get(2)?;
+                    Ok(CompletionSummary {
+                        completed: row.get(0)?,
+                        on_time: row.get::<_, Option<u64>>(1)?.unwrap_or(0),
+                        average_lead_time: average_days
+                            .map(|days| Duration::seconds((days * 86400.0) as i64)),
+                    })
+                },
+            )
+            .unwrap_or(CompletionSummary {
+                completed: 0,
+                on_time: 0,
+                average_lead_time: None,
+            })
+    }
+
+    // TEMPLATES
+    pub fn get_templates(&self) -> Vec<TaskTemplate> {
+        let mut stmt = self
+            .db_con
+            .prepare("SELECT Id, Name, Title, Description, PriorityLevel, DueOffsetDays FROM task_template ORDER BY Name;")
+            .expect("Failed to prepare for template retrieval");
+
+        let results = stmt.query_map([], |row| {
+            Ok(TaskTemplate {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                title: row.get(2)?,
+                description: row.get(3)?,
+                priority: row.get(4)?,
+                due_offset_days: row.get(5)?,
+                subtasks: Vec::new(),
+            })
+        });
+        let mut templates: Vec<TaskTemplate> = match results {
+            Ok(templates) => templates.filter_map(|template| template.ok()).collect(),
+            Err(_) => Vec::new(),
+        };
+
+        let mut stmt = self
+            .db_con
+            .prepare("SELECT Title FROM task_template_subtask WHERE TemplateId = ? ORDER BY Position;")
+            .expect("Failed to prepare for subtask retrieval");
+        for template in templates.iter_mut() {
+            if let Ok(subtasks) = stmt.query_map([template.id], |row| row.get(0)) {
+                template.subtasks = subtasks.filter_map(|subtask| subtask.ok()).collect();
+            }
+        }
+        templates
+    }
+
+    // A template with the same name is replaced
+    pub fn save_template(&self, template: &TaskTemplate) -> Result<()> {
+        let transaction = self.write_transaction()?;
+        transaction.execute(
+            "DELETE FROM task_template_subtask WHERE TemplateId IN (SELECT Id FROM task_template WHERE Name = ?);",
+            [&template.name],
+        )?;
+        transaction.execute("DELETE FROM task_template WHERE Name = ?;", [&template.name])?;
+        transaction.execute(
+            "INSERT INTO task_template (Name, Title, Description, PriorityLevel, DueOffsetDays) VALUES (?1, ?2, ?3, ?4, ?5);",
+            (&template.name, &template.title, &template.description, &template.priority, template.due_offset_days),
+        )?;
+        let template_id = transaction.last_insert_rowid();
+        for (position, subtask) in template.subtasks.iter().enumerate() {
+            transaction.execute(
+                "INSERT INTO task_template_subtask (TemplateId, Position, Title) VALUES (?1, ?2, ?3);",
+                (template_id, position as i64, subtask),
+            )?;
+        }
+        transaction.commit()
+    }
+
+    pub fn delete_template(&self, template_id: i32) -> Result<()> {
+        let transaction = self.write_transaction()?;
+        transaction.execute("DELETE FROM task_template_subtask WHERE TemplateId = ?;", [template_id])?;
+        transaction.execute("DELETE FROM task_template WHERE Id = ?;", [template_id])?;
+        transaction.commit()
+    }
+
+    // SETTINGS
+    pub fn get_setting(&self, key: &str) -> Option<String> {
+        self.db_con
+            .query_row("SELECT Value FROM setting WHERE Key = ?;", [key], |row| {
+                row.get(0)
+            })
+            .optional()
+            .unwrap_or(None)
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<usize> {
+        self.db_con.execute(
+            "INSERT INTO setting (Key, Value) VALUES (?1, ?2) ON CONFLICT(Key) DO UPDATE SET Value = ?2;",
+            (key, value),
+        )
+    }
+
+    // CHANGE DETECTION
+    // Changes whenever another connection commits to the database, but not on own writes
+    pub fn data_version(&self) -> i64 {
+        self.db_con
+            .query_row("PRAGMA data_version;", [], |row| row.get(0))
+            .unwrap_or(0)
+    }
+}
+
+// Message shown to the user when a write fails
+pub fn write_error(error: rusqlite::Error) -> String {
+    match error.sqlite_error_code() {
+        Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked) => {
+            "The database is busy with another instance, try again".to_string()
+        }
+        _ => format!("Failed to save to the database: {}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage_with_tasks(titles: &[&str]) -> (Storage, Vec<i32>) {
+        let storage = Storage {
+            db_con: Connection::open_in_memory().unwrap(),
+        };
+        storage.create_table_if_not_exists();
+        let task_ids = titles
+            .iter()
+            .map(|title| {
+                let task = Task {
+                    title: title.to_string(),
+                    ..Task::default()
+                };
+                storage.insert_task(&task).unwrap()
+            })
+            .collect();
+        (storage, task_ids)
+    }
+
+    fn blocker_ids(storage: &Storage, task_id: i32) -> Vec<i32> {
+        storage.get_blockers(task_id).into_iter().filter_map(|task| task.id).collect()
+    }
+
+    #[test]
+    fn refuses_a_task_waiting_for_itself() {
+        let (storage, ids) = storage_with_tasks(&["A"]);
+        assert_eq!(storage.add_dependencies(ids[0], &[ids[0]]), Err("A cannot wait for itself".to_string()));
+        assert!(blocker_ids(&storage, ids[0]).is_empty());
+    }
+
+    #[test]
+    fn refuses_a_direct_cycle() {
+        let (storage, ids) = storage_with_tasks(&["A", "B"]);
+        assert_eq!(storage.add_dependencies(ids[0], &[ids[1]]), Ok(1));
+        assert_eq!(storage.add_dependencies(ids[1], &[ids[0]]), Err("A already waits for B".to_string()));
+        assert!(blocker_ids(&storage, ids[1]).is_empty());
+    }
+
+    #[test]
+    fn refuses_a_transitive_cycle() {
+        let (storage, ids) = storage_with_tasks(&["A", "B", "C"]);
+        assert_eq!(storage.add_dependencies(ids[0], &[ids[1]]), Ok(1));
+        assert_eq!(storage.add_dependencies(ids[1], &[ids[2]]), Ok(1));
+        assert_eq!(storage.add_dependencies(ids[2], &[ids[0]]), Err("A already waits for C".to_string()));
+        assert!(blocker_ids(&storage, ids[2]).is_empty());
+    }
+
+    #[test]
+    fn writes_nothing_when_one_of_several_blockers_is_refused() {
+        let (storage, ids) = storage_with_tasks(&["A", "B", "C"]);
+        assert_eq!(storage.add_dependencies(ids[0], &[ids[1]]), Ok(1));
+        assert!(storage.add_dependencies(ids[1], &[ids[2], ids[0]]).is_err());
+        assert!(blocker_ids(&storage, ids[1]).is_empty());
+        assert_eq!(storage.add_dependencies(ids[0], &[ids[1], ids[2]]), Ok(1));
     }
 }
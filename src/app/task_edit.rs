@@ -1,10 +1,11 @@
-use chrono::NaiveDateTime;
-use ratatui::style::{Color, Style};
+use chrono::{Duration, Utc};
 use ratatui::text::{Line, Span};
-use std::vec;
 
-use crate::app::models::Task;
-use crate::app::storage::Storage;
+use crate::app::models::{
+    format_reminders, format_tags, parse_date, parse_reminders, parse_tags, Estimate, Priority, Task,
+    TaskTemplate, DATE_FORMAT,
+};
+use crate::app::storage::{write_error, Storage};
 use derivative::Derivative;
 
 use super::ui::App;
@@ -16,11 +17,20 @@ use super::ui::App;
 pub struct TaskEditDialogState {
     pub dialog_active: bool,
     task_id: Option<i32>,
+    // Version of the stored task when the editing started
+    task_version: i64,
     content: Option<TaskEditDialogContent>,
+    // The stored task, when it was changed elsewhere while being edited
+    pub conflict: Option<Task>,
+    // Titles of the subtasks to create along with a new task, from its template
+    subtasks: Vec<String>,
     error_message: Option<String>,
     cursor_position: Option<(usize, usize)>,
 }
 
+// Number of input lines of the dialog
+const FIELD_COUNT: usize = 8;
+
 // Current content of the task being edited/created
 #[derive(Derivative)]
 #[derivative(Default)]
@@ -28,7 +38,12 @@ struct TaskEditDialogContent {
     title: String,
     description: String,
     due_date: String,
-    priority: i32,
+    priority: Priority,
+    tags: String,
+    reminders: String,
+    estimate: String,
+    defer_until: String,
+    completed: bool,
 }
 
 // Refer to https://stackoverflow.com/a/66609806
@@ -38,7 +53,12 @@ impl<'a> Default for &'a TaskEditDialogContent {
             title: String::new(),
             description: String::new(),
             due_date: String::new(),
-            priority: 0,
+            priority: Priority::None,
+            tags: String::new(),
+            reminders: String::new(),
+            estimate: String::new(),
+            defer_until: String::new(),
+            completed: false,
         };
         &VALUE
     }
@@ -49,19 +69,47 @@ impl TaskEditDialogState {
     pub fn create_a_new_task(&mut self) {
         self.dialog_active = true;
         self.task_id = None;
+        self.conflict = None;
+        self.subtasks = Vec::new();
         self.content = Some(TaskEditDialogContent::default());
     }
 
+    // Opens the dialog for a new task filled in from the template
+    pub fn create_from_template(&mut self, template: &TaskTemplate) {
+        self.create_a_new_task();
+        self.cursor_position = Some((0, 0));
+        self.subtasks = template.subtasks.clone();
+        let due_date = Utc::now() + Duration::days(template.due_offset_days);
+        self.content = Some(TaskEditDialogContent {
+            title: template.title.clone(),
+            description: template.description.clone(),
+            due_date: due_date.format(DATE_FORMAT).to_string(),
+            priority: template.priority,
+            ..TaskEditDialogContent::default()
+        });
+    }
+
     // Opens the dialog and prepares to accept an input for the existing task
     pub fn edit_task(&mut self, task: &Task) {
         self.dialog_active = true;
         self.task_id = task.id;
+        self.task_version = task.version;
+        self.conflict = None;
+        self.subtasks = Vec::new();
         self.cursor_position = Some((0, 0));
         self.content = Some(TaskEditDialogContent {
             title: task.title.clone(),
             description: task.description.clone(),
-            due_date: task.due_date.format("%d.%m.%Y").to_string(),
+            due_date: task.due_date.format(DATE_FORMAT).to_string(),
             priority: task.priority,
+            tags: format_tags(&task.tags),
+            reminders: format_reminders(&task.reminders),
+            estimate: task.estimate.map(|estimate| estimate.label()).unwrap_or_default(),
+            defer_until: task
+                .defer_until
+                .map(|date| date.format(DATE_FORMAT).to_string())
+                .unwrap_or_default(),
+            completed: task.completed,
         });
     }
 
@@ -69,8 +117,7 @@ impl TaskEditDialogState {
     // An overflow should be prevented, and the horizontal cursor position should be preserved if possible
     pub fn move_cursor_down(&mut self) {
         let cursor_position = self.cursor_position.unwrap_or((0, 0));
-        // TODO: Make this 3 dynamic
-        let future_y_position = (cursor_position.1 + 1).min(3);
+        let future_y_position = (cursor_position.1 + 1).min(FIELD_COUNT - 1);
         self.cursor_position = Some((
             (cursor_position.0).min(self.content_of_string_at_y_pos(future_y_position).len()),
             future_y_position,
@@ -82,7 +129,7 @@ impl TaskEditDialogState {
     pub fn move_cursor_up(&mut self) {
         let cursor_position = self.cursor_position.unwrap_or((0, 0));
         if cursor_position.1 > 0 {
-            self.cursor_position = Some((cursor_position.0, (cursor_position.1 - 1).max(0)));
+            self.cursor_position = Some((cursor_position.0, cursor_position.1 - 1));
         }
     }
 
@@ -122,6 +169,10 @@ impl TaskEditDialogState {
                 0 => content.title.remove(cursor_position.0),
                 1 => content.description.remove(cursor_position.0),
                 2 => content.due_date.remove(cursor_position.0),
+                4 => content.tags.remove(cursor_position.0),
+                5 => content.reminders.remove(cursor_position.0),
+                6 => content.estimate.remove(cursor_position.0),
+                7 => content.defer_until.remove(cursor_position.0),
                 _ => ' ',
             },
             None => return,
@@ -133,7 +184,7 @@ impl TaskEditDialogState {
     // Returns the content of the string at the given y position
     // Think of this as a mapper of vertical cursor position to the string content
     fn content_of_string_at_y_pos(&self, y_position: usize) -> String {
-        return match y_position {
+        match y_position {
             0 => self.content.as_ref().unwrap_or_default().title.clone(),
             1 => self
                 .content
@@ -147,29 +198,63 @@ impl TaskEditDialogState {
                 .as_ref()
                 .unwrap_or_default()
                 .priority
+                .label()
                 .to_string(),
+            4 => self.content.as_ref().unwrap_or_default().tags.clone(),
+            5 => self.content.as_ref().unwrap_or_default().reminders.clone(),
+            6 => self.content.as_ref().unwrap_or_default().estimate.clone(),
+            7 => self.content.as_ref().unwrap_or_default().defer_until.clone(),
             _ => "".to_string(),
-        };
+        }
     }
 
     // Saves the task to the database
     pub fn save_task(&mut self, storage: &Storage) {
         let content = self.content.as_ref().unwrap_or_default();
         // Validate the input
-        let date = match NaiveDateTime::parse_from_str(
-            format!("{} 00:00:00Z", content.due_date).as_str(),
-            "%d.%m.%Y %H:%M:%SZ",
-        ) {
-            Ok(date) => date,
-            Err(_e) => {
+        let date = match parse_date(&content.due_date) {
+            Some(date) => date,
+            None => {
                 self.error_message = Some("Date should be in format dd.mm.yyyy".to_string());
                 return;
             }
         };
-        if content.title.len() == 0 {
+        let reminders = match parse_reminders(&content.reminders) {
+            Some(reminders) => reminders,
+            None => {
+                self.error_message =
+                    Some("Reminders should be like 1d 2h 30m, or 0 for the due time".to_string());
+                return;
+            }
+        };
+        let estimate = if content.estimate.trim().is_empty() {
+            None
+        } else {
+            match Estimate::parse(&content.estimate) {
+                Some(estimate) => Some(estimate),
+                None => {
+                    self.error_message =
+                        Some("Estimate should be like 30m, 2h or 3pts".to_string());
+                    return;
+                }
+            }
+        };
+        let defer_until = if content.defer_until.trim().is_empty() {
+            None
+        } else {
+            match parse_date(&content.defer_until) {
+                Some(date) => Some(date),
+                None => {
+                    self.error_message =
+                        Some("Start date should be in format dd.mm.yyyy or empty".to_string());
+                    return;
+                }
+            }
+        };
+        if content.title.is_empty() {
             self.error_message = Some("Title cannot be empty".to_string());
             return;
-        } else if content.description.len() == 0 {
+        } else if content.description.is_empty() {
             self.error_message = Some("Description cannot be empty".to_string());
             return;
         }
@@ -179,26 +264,107 @@ impl TaskEditDialogState {
             id: self.task_id,
             title: content.title.clone(),
             description: content.description.clone(),
-            due_date: date.and_utc(),
+            due_date: date,
             priority: content.priority,
-            completed: false,
+            tags: parse_tags(&content.tags),
+            reminders,
+            estimate,
+            defer_until,
+            completed: content.completed,
+            version: self.task_version,
+            ..Task::default()
         };
 
         // Update/insert the task and close the window
-        if self.task_id.is_some() {
-            storage.update_task(&task).expect("Failed to update a task");
+        // The window stays open if the task was changed elsewhere meanwhile, or could not be saved
+        if let Some(task_id) = self.task_id {
+            match storage.update_task(&task) {
+                Ok(0) => {
+                    match storage.get_task(task_id) {
+                        Some(stored_task) => self.conflict = Some(stored_task),
+                        None => self.error_message = Some("The task no longer exists".to_string()),
+                    }
+                    return;
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    self.error_message = Some(write_error(error));
+                    return;
+                }
+            }
         } else {
-            storage.insert_task(&task).expect("Failed to create a task");
+            // The subtasks share the due date and priority, and the task waits for them
+            let subtasks = self
+                .subtasks
+                .iter()
+                .map(|title| Task {
+                    title: title.clone(),
+                    description: format!("Subtask of {}", task.title),
+                    due_date: task.due_date,
+                    priority: task.priority,
+                    ..Task::default()
+                })
+                .collect::<Vec<Task>>();
+            if let Err(error) = storage.insert_task_with_subtasks(&task, &subtasks) {
+                self.error_message = Some(write_error(error));
+                return;
+            }
         }
 
         self.error_message = None;
         self.dialog_active = false;
     }
 
+    // Resolve a conflict by saving the own changes over the stored ones
+    pub fn overwrite_conflict(&mut self, storage: &Storage) {
+        if let Some(stored_task) = self.conflict.take() {
+            self.task_version = stored_task.version;
+            self.save_task(storage);
+        }
+    }
+
+    // Resolve a conflict by dropping the own changes and editing the stored task
+    pub fn load_conflict(&mut self) {
+        if let Some(stored_task) = self.conflict.take() {
+            self.edit_task(&stored_task);
+        }
+    }
+
+    // Fields whose stored value differs from the one being edited
+    fn conflicting_fields(&self, stored_task: &Task) -> Vec<&'static str> {
+        let content = self.content.as_ref().unwrap_or_default();
+        [
+            ("title", stored_task.title != content.title),
+            ("description", stored_task.description != content.description),
+            ("due date", stored_task.due_date.format(DATE_FORMAT).to_string() != content.due_date),
+            ("priority", stored_task.priority != content.priority),
+            ("tags", format_tags(&stored_task.tags) != content.tags),
+            ("reminders", format_reminders(&stored_task.reminders) != content.reminders),
+            (
+                "estimate",
+                stored_task.estimate.map(|estimate| estimate.label()).unwrap_or_default()
+                    != content.estimate,
+            ),
+            (
+                "start date",
+                stored_task
+                    .defer_until
+                    .map(|date| date.format(DATE_FORMAT).to_string())
+                    .unwrap_or_default()
+                    != content.defer_until,
+            ),
+            ("completed", stored_task.completed != content.completed),
+        ]
+        .into_iter()
+        .filter(|(_, differs)| *differs)
+        .map(|(field, _)| field)
+        .collect()
+    }
+
     // Handles the input of a char by appending it to the value of the currently active field
     pub fn input(&mut self, to_insert: char) {
         let mut cursor_position = self.cursor_position.unwrap_or((0, 0));
-        if self.content_of_string_at_y_pos(cursor_position.1).len() == 0 {
+        if self.content_of_string_at_y_pos(cursor_position.1).is_empty() {
             self.cursor_position = Some((0, cursor_position.1));
             cursor_position = self.cursor_position.unwrap_or((0, 0));
         }
@@ -209,10 +375,27 @@ impl TaskEditDialogState {
                 1 => content.description.insert(cursor_position.0, to_insert),
                 2 => content.due_date.insert(cursor_position.0, to_insert),
                 3 => {
-                    if vec!['0', '1', '2'].contains(&to_insert) {
-                        content.priority = to_insert.to_string().parse::<i32>().unwrap_or(0)
+                    // Priority is not typed in; it is cycled or chosen by its level
+                    match to_insert {
+                        '+' => content.priority = content.priority.raised(),
+                        '-' => content.priority = content.priority.lowered(),
+                        '0'..='4' => {
+                            content.priority = Priority::from_level(to_insert as i64 - '0' as i64)
+                        }
+                        _ => {
+                            self.error_message =
+                                Some("Priority: use + and - or a level 0-4".to_string());
+                            return;
+                        }
                     }
+                    self.error_message = None;
+                    self.cursor_position = Some((0, 3));
+                    return;
                 }
+                4 => content.tags.insert(cursor_position.0, to_insert),
+                5 => content.reminders.insert(cursor_position.0, to_insert),
+                6 => content.estimate.insert(cursor_position.0, to_insert),
+                7 => content.defer_until.insert(cursor_position.0, to_insert),
                 _ => {}
             },
             None => return,
@@ -224,9 +407,9 @@ impl TaskEditDialogState {
 
 // Returns the UI content for the task edit dialog
 pub fn get_task_edit_ui<'a>(app: &'a App<'a>) -> Vec<Line<'a>> {
-    const GRAY_TEXT: Style = Style::new().fg(Color::Rgb(62, 62, 62));
-    const WHITE_TEXT: Style = Style::new().fg(Color::White);
-    const BLACK_ON_WHITE: Style = Style::new().fg(Color::Black).bg(Color::White);
+    let placeholder_style = app.theme.placeholder;
+    let text_style = app.theme.text;
+    let cursor_style = app.theme.cursor;
     let mut text = Vec::new();
 
     struct TextDialogInputLine {
@@ -236,7 +419,7 @@ pub fn get_task_edit_ui<'a>(app: &'a App<'a>) -> Vec<Line<'a>> {
     }
 
     // Define the lines (input fie) of the dialog
-    let lines = vec![
+    let lines = [
         TextDialogInputLine {
             prefix: "Title:       ".into(),
             placeholder: "My task name".into(),
@@ -254,8 +437,28 @@ pub fn get_task_edit_ui<'a>(app: &'a App<'a>) -> Vec<Line<'a>> {
         },
         TextDialogInputLine {
             prefix: "Priority:    ".into(),
-            placeholder: "0".into(),
-            value: app.task_edit_dialog_state.content.as_ref().unwrap_or_default().priority.to_string(),
+            placeholder: "None".into(),
+            value: app.task_edit_dialog_state.content.as_ref().unwrap_or_default().priority.label().to_string(),
+        },
+        TextDialogInputLine {
+            prefix: "Tags:        ".into(),
+            placeholder: "#home #errands".into(),
+            value: app.task_edit_dialog_state.content.as_ref().unwrap_or_default().tags.clone(),
+        },
+        TextDialogInputLine {
+            prefix: "Reminders:   ".into(),
+            placeholder: "1d 0".into(),
+            value: app.task_edit_dialog_state.content.as_ref().unwrap_or_default().reminders.clone(),
+        },
+        TextDialogInputLine {
+            prefix: "Estimate:    ".into(),
+            placeholder: "2h or 3pts".into(),
+            value: app.task_edit_dialog_state.content.as_ref().unwrap_or_default().estimate.clone(),
+        },
+        TextDialogInputLine {
+            prefix: "Start date:  ".into(),
+            placeholder: "20.11.2023".into(),
+            value: app.task_edit_dialog_state.content.as_ref().unwrap_or_default().defer_until.clone(),
         },
     ];
 
@@ -268,24 +471,24 @@ pub fn get_task_edit_ui<'a>(app: &'a App<'a>) -> Vec<Line<'a>> {
         let mut spans = Vec::new();
 
         // Each line starts with a prefix, for example "Title: "
-        spans.push(Span::styled(line.prefix.clone(), WHITE_TEXT));
+        spans.push(Span::styled(line.prefix.clone(), text_style));
 
-        if line.value.len() == 0 {
+        if line.value.is_empty() {
             // If the line is empty, a placeholder is displayed
             if cursor_position.1 == i {
                 // Line is selected. First char is highlighted, the rest is gray
                 spans.push(Span::styled(
                     line.placeholder.chars().take(1).collect::<String>(),
-                    BLACK_ON_WHITE,
+                    cursor_style,
                 ));
                 spans.push(Span::styled(
                     line.placeholder.chars().skip(1).collect::<String>(),
-                    GRAY_TEXT,
+                    placeholder_style,
                 ));
 
             } else {
                 // Line is not selected. All chars are gray
-                spans.push(Span::styled(line.placeholder.clone(), GRAY_TEXT));
+                spans.push(Span::styled(line.placeholder.clone(), placeholder_style));
             }
 
         } else {
@@ -298,7 +501,7 @@ pub fn get_task_edit_ui<'a>(app: &'a App<'a>) -> Vec<Line<'a>> {
                         .chars()
                         .take(cursor_position.0)
                         .collect::<String>(),
-                    WHITE_TEXT,
+                    text_style,
                 ));
                 spans.push(Span::styled(
                     line.value
@@ -307,7 +510,7 @@ pub fn get_task_edit_ui<'a>(app: &'a App<'a>) -> Vec<Line<'a>> {
                         .skip(cursor_position.0)
                         .take(1)
                         .collect::<String>(),
-                    BLACK_ON_WHITE,
+                    cursor_style,
                 ));
                 spans.push(Span::styled(
                     line.value
@@ -315,40 +518,63 @@ pub fn get_task_edit_ui<'a>(app: &'a App<'a>) -> Vec<Line<'a>> {
                         .chars()
                         .skip(cursor_position.0 + 1)
                         .collect::<String>(),
-                    WHITE_TEXT,
+                    text_style,
                 ));
 
                 if cursor_position.0 == line.value.len() {
-                    spans.push(Span::styled(" ", BLACK_ON_WHITE));
+                    spans.push(Span::styled(" ", cursor_style));
                 }
             } else {
                 // All chars are white if the line is not selected
-                spans.push(Span::styled(line.value.clone(), WHITE_TEXT));
+                spans.push(Span::styled(line.value.clone(), text_style));
             }
         }
 
         text.push(Line::from(spans));
     }
 
+    if !app.task_edit_dialog_state.subtasks.is_empty() {
+        text.push(Line::from(vec![Span::styled(
+            format!("Subtasks:    {}", app.task_edit_dialog_state.subtasks.join(", ")),
+            placeholder_style,
+        )]));
+    }
+
     text.push(Line::raw("\n"));
 
     // Display the error message if there is one
-    match app.task_edit_dialog_state.error_message {
-        Some(ref error_message) => {
-            text.push(Line::from(vec![Span::styled(
-                error_message,
-                Style::new().fg(Color::Red),
-            )]));
-            text.push(Line::raw("\n"));
-        }
-        None => {}
+    if let Some(ref error_message) = app.task_edit_dialog_state.error_message {
+        text.push(Line::from(vec![Span::styled(
+            error_message,
+            app.theme.error,
+        )]));
+        text.push(Line::raw("\n"));
+    }
+
+    // Display the conflict with the stored task instead of the help text
+    if let Some(ref stored_task) = app.task_edit_dialog_state.conflict {
+        let fields = app.task_edit_dialog_state.conflicting_fields(stored_task);
+        let changes = if fields.is_empty() {
+            String::new()
+        } else {
+            format!(" (differs in {})", fields.join(", "))
+        };
+        text.push(Line::from(vec![Span::styled(
+            format!("The task was changed elsewhere since it was opened{}", changes),
+            app.theme.error,
+        )]));
+        text.push(Line::from(vec![Span::styled(
+            "o - overwrite with yours, r - load the stored task, Esc - keep editing",
+            text_style,
+        )]));
+        return text;
     }
 
     // Display the help text
     text.push(Line::from(vec![Span::styled(
-        "\nEnter - save, Esc - cancel",
-        WHITE_TEXT,
+        "\nEnter - save, Esc - cancel, +/- - change priority",
+        text_style,
     )]));
 
-    return text;
+    text
 }
@@ -1,230 +1,661 @@
-use chrono::Utc;
-use now::DateTimeNow;
-use ratatui::style::{Color, Style, Stylize};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeSet;
 use ratatui::text::{Line, Span};
-use std::ops::Add;
 
 use ratatui::widgets::*;
 
-use crate::app::models::Task;
-use crate::app::storage::Storage;
+use crate::app::focus::{rank, score, FocusWeights};
+use crate::app::keymap::{Action, KeyMap};
+use crate::app::models::{
+    format_reminders, format_tags, format_tracked, EstimateTotal, Priority, RunningTimer, Task, DATE_FORMAT,
+};
+use crate::app::query::{TaskFilter, TaskQuery, TaskScope};
+use crate::app::sort::{SortDirection, SortKey, SortSpec, SortedBy};
+use crate::app::storage::{write_error, Storage};
+use crate::app::theme::Theme;
 
 use super::ui::App;
 
-// Possible task list sorting orders
-#[derive(PartialEq)]
-pub enum SortedBy {
-    ByDueDate,
-    ByName,
-    ByPriority,
-}
+// Key of the setting that keeps the sort across sessions
+const SORT_SETTING: &str = "sort";
 
 pub struct TaskList<'a, Task> {
     pub state: ListState,
+    // Tasks of the current page
     pub items: Vec<Task>,
     storage: &'a Storage,
-    sorted_by: Option<SortedBy>,
+    pub sort: SortSpec,
+    pub filter: TaskFilter,
+    pub page: usize,
+    page_size: usize,
+    // Number of tasks matching the filter on all pages
+    pub total: usize,
+    // Ids of the tasks marked for a bulk operation, on any page
+    pub marked: BTreeSet<i32>,
+    mark_anchor: Option<i32>,
+    // Open tasks ranked by score instead of sorted
+    pub focus: bool,
+    pub focus_weights: FocusWeights,
+    // Counts the reads of the items, so views built from the tasks know when to rebuild
+    pub generation: u64,
 }
 
 impl<'a> TaskList<'a, Task> {
     // Initialize a task list with items from the database
-    pub fn with_items_from_storage(storage: &'a Storage) -> TaskList<'a, Task> {
-        TaskList {
+    // The sort of the last session wins over the default one
+    pub fn with_items_from_storage(
+        storage: &'a Storage,
+        default_sort: SortSpec,
+        page_size: usize,
+        focus_weights: FocusWeights,
+    ) -> TaskList<'a, Task> {
+        let sort = storage
+            .get_setting(SORT_SETTING)
+            .and_then(|spec| SortSpec::parse(&spec).ok())
+            .unwrap_or(default_sort);
+
+        let mut task_list = TaskList {
             state: ListState::default(),
-            items: storage.get_all_tasks(),
-            storage: storage,
-            sorted_by: None,
-        }
+            items: Vec::new(),
+            storage,
+            sort,
+            filter: TaskFilter::default(),
+            page: 0,
+            page_size: page_size.max(1),
+            total: 0,
+            marked: BTreeSet::new(),
+            mark_anchor: None,
+            focus: false,
+            focus_weights,
+            generation: 0,
+        };
+        task_list.update_items();
+        task_list
     }
 
-    // Refresh the items of this list with the items from the database
+    // Refresh the items of this list with the current page from the database
+    // The page and selection are kept in range if tasks disappeared
     pub fn update_items(&mut self) {
-        self.items = self.storage.get_all_tasks();
+        self.generation += 1;
+        if self.focus {
+            self.update_focus_items();
+        } else {
+            self.total = self.storage.count_tasks(&self.filter);
+            self.page = self.page.min(self.page_count() - 1);
+            self.items = self.storage.query_tasks(&TaskQuery {
+                filter: self.filter.clone(),
+                sort: self.sort.clone(),
+                limit: Some(self.page_size),
+                offset: self.page * self.page_size,
+            });
+        }
+
+        if let Some(i) = self.state.selected() {
+            if self.items.is_empty() {
+                self.state.select(None);
+            } else if i >= self.items.len() {
+                self.state.select(Some(self.items.len() - 1));
+            }
+        }
+    }
+
+    // The score is not known to SQLite, so all open tasks are ranked and the page is cut out here
+    // Equal scores go to the task due first
+    fn update_focus_items(&mut self) {
+        let open_tasks = self.storage.query_tasks(&TaskQuery {
+            filter: TaskFilter {
+                completed: Some(false),
+                ..self.filter.clone()
+            },
+            sort: SortSpec {
+                keys: vec![SortKey {
+                    by: SortedBy::DueDate,
+                    direction: SortDirection::Ascending,
+                }],
+            },
+            ..TaskQuery::default()
+        });
+        let ranked = rank(open_tasks, &self.focus_weights, Utc::now());
+
+        self.total = ranked.len();
+        self.page = self.page.min(self.page_count() - 1);
+        self.items = ranked
+            .into_iter()
+            .skip(self.page * self.page_size)
+            .take(self.page_size)
+            .collect();
+    }
+
+    // Switch between the focus ranking and the sort
+    pub fn toggle_focus(&mut self) {
+        self.focus = !self.focus;
+        self.page = 0;
+        self.state.select(None);
+        self.update_items();
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.total.div_ceil(self.page_size).max(1)
     }
 
-    // Move the selection to the next item
-    // Coppied from original example
+    // Switch to the given page and select the item at the given index on it
+    fn go_to_page(&mut self, page: usize, selected: Option<usize>) {
+        self.page = page;
+        self.update_items();
+        if self.items.is_empty() {
+            self.state.select(None);
+        } else {
+            self.state.select(selected.map(|i| i.min(self.items.len() - 1)));
+        }
+    }
+
+    pub fn next_page(&mut self) {
+        if self.page + 1 < self.page_count() {
+            self.go_to_page(self.page + 1, Some(0));
+        }
+    }
+
+    pub fn previous_page(&mut self) {
+        if self.page > 0 {
+            self.go_to_page(self.page - 1, Some(0));
+        }
+    }
+
+    // Move the selection to the next item, continuing on the next page
+    // Based on original example
+    // Nothing is selected in an empty list
     pub fn next(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if self.items.len() == 0 || i >= self.items.len() - 1 {
-                    0
-                } else {
-                    i + 1
-                }
+        if self.items.is_empty() {
+            self.state.select(None);
+            return;
+        }
+        match self.state.selected() {
+            Some(i) if i >= self.items.len() - 1 => {
+                let page = if self.page + 1 < self.page_count() { self.page + 1 } else { 0 };
+                self.go_to_page(page, Some(0));
             }
-            None => 0,
-        };
-        self.state.select(Some(i));
+            Some(i) => self.state.select(Some(i + 1)),
+            None => self.state.select(Some(0)),
+        }
     }
 
-    // Move the selection to the previous item
-    // Coppied from original example
+    // Move the selection to the previous item, continuing on the previous page
+    // Based on original example
     pub fn previous(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if self.items.len() == 0 {
-                    0
-                } else if i == 0 {
-                    self.items.len() - 1
-                } else {
-                    i - 1
-                }
+        if self.items.is_empty() {
+            self.state.select(None);
+            return;
+        }
+        match self.state.selected() {
+            Some(0) => {
+                let page = if self.page > 0 { self.page - 1 } else { self.page_count() - 1 };
+                self.go_to_page(page, Some(usize::MAX));
             }
-            None => 0,
+            Some(i) => self.state.select(Some(i - 1)),
+            None => self.state.select(Some(0)),
+        }
+    }
+
+    // Show or hide the completed tasks
+    pub fn toggle_hide_completed(&mut self) {
+        self.filter.completed = match self.filter.completed {
+            Some(false) => None,
+            _ => Some(false),
         };
-        self.state.select(Some(i));
+        self.page = 0;
+        self.update_items();
+    }
+
+    // Show or hide the tasks deferred to a later day
+    pub fn toggle_deferred(&mut self) {
+        self.filter.include_deferred = !self.filter.include_deferred;
+        self.update_items();
+    }
+
+    // Show only the open tasks that are not blocked, or all tasks again
+    pub fn toggle_actionable(&mut self) {
+        self.filter.actionable = !self.filter.actionable;
+        self.page = 0;
+        self.update_items();
     }
 
     pub fn unselect(&mut self) {
         self.state.select(None);
     }
 
-    // Change the state of the task to completed/to do; Save in database.
-    pub fn toggle_completed(&mut self) {
-        self.apply_for_selected_task({
-            |task| {
-                task.completed = !task.completed;
-                self.storage
-                    .update_task(task)
-                    .expect("Failed to update a task");
+    // Mark or unmark the selected task for a bulk operation
+    pub fn toggle_mark(&mut self) {
+        if let Some(task_id) = self.get_selected().and_then(|task| task.id) {
+            if !self.marked.remove(&task_id) {
+                self.marked.insert(task_id);
             }
-        });
+            self.mark_anchor = Some(task_id);
+        }
     }
 
-    // Perform a function on the object of the selected task
-    fn apply_for_selected_task(&mut self, function: impl Fn(&mut Task)) {
-        match self.state.selected() {
-            Some(i) => {
-                match self.items.get_mut(i) {
-                    Some(item) => {
-                        function(item);
-                    }
-                    None => return,
-                };
-            }
-            None => {}
+    // Mark all tasks on the page between the last marked task and the selected one
+    pub fn mark_range(&mut self) {
+        let selected = match self.state.selected() {
+            Some(selected) if !self.items.is_empty() => selected.min(self.items.len() - 1),
+            _ => return,
         };
-    }
+        let anchor = self
+            .mark_anchor
+            .and_then(|anchor| self.items.iter().position(|task| task.id == Some(anchor)))
+            .unwrap_or(selected);
 
-    // Delete the selected task from database; Update the items
-    pub fn delete_selected(&mut self) {
-        self.apply_for_selected_task({
-            |task| {
-                self.storage
-                    .delete_task(task.id.unwrap_or(-1))
-                    .expect("Failed to update a task");
+        for task in &self.items[anchor.min(selected)..=anchor.max(selected)] {
+            if let Some(task_id) = task.id {
+                self.marked.insert(task_id);
             }
-        });
-        self.update_items();
+        }
+        self.mark_anchor = self.get_selected().and_then(|task| task.id);
     }
 
-    // Get the selected task
-    pub fn get_selected(&self) -> Option<&Task> {
-        match self.state.selected() {
-            Some(i) => self.items.get(i),
-            None => None,
+    pub fn clear_marks(&mut self) {
+        self.marked.clear();
+        self.mark_anchor = None;
+    }
+
+    // Ids of the tasks an action applies to: the marked ones, or else the selected one
+    pub fn target_ids(&self) -> Vec<i32> {
+        if self.marked.is_empty() {
+            self.get_selected().and_then(|task| task.id).into_iter().collect()
+        } else {
+            self.marked.iter().copied().collect()
         }
     }
 
-    // Get the uncompleted tasks
-    pub fn get_uncompleted(&self) -> Vec<&Task> {
-        return self
-            .items
+    // Titles of the given tasks, for example to list them before they are changed
+    pub fn titles(&self, task_ids: &[i32]) -> Vec<String> {
+        task_ids
             .iter()
-            .filter(|task| !task.completed)
-            .collect::<Vec<&Task>>();
+            .filter_map(|task_id| self.storage.get_task(*task_id))
+            .map(|task| task.title)
+            .collect()
     }
 
-    // Get the tasks due next week
-    pub fn get_due_next_week(&self) -> Vec<&Task> {
-        let next_week = Utc::now().add(chrono::Duration::weeks(1));
-        return self
-            .items
-            .iter()
-            .filter(|task| !task.completed && task.due_date < next_week)
-            .collect::<Vec<&Task>>();
+    // Apply the change to the given tasks in one transaction; Update the items
+    // The marks are kept, so several changes can be applied to the same tasks
+    pub fn update_tasks(&mut self, task_ids: &[i32], change: impl Fn(&mut Task)) -> Result<(), String> {
+        let updated = self.storage.update_tasks(task_ids, change);
+        self.update_items();
+        updated.map(|_| ()).map_err(write_error)
+    }
+
+    // Marked tasks are all completed, or all reopened if they already are completed
+    pub fn all_completed(&self, task_ids: &[i32]) -> bool {
+        task_ids.iter().all(|task_id| {
+            self.storage
+                .get_task(*task_id)
+                .is_some_and(|task| task.completed)
+        })
     }
 
-    // Get the late tasks
-    pub fn get_late(&self) -> Vec<&Task> {
-        return self
-            .items
+    // Change the state of the given tasks to completed/to do; Save in database.
+    // Returns the titles of the tasks that are no longer blocked
+    pub fn set_completed(&mut self, task_ids: &[i32], completed: bool) -> Result<Vec<String>, String> {
+        let waiting = task_ids
             .iter()
-            .filter(|task| !task.completed && task.due_date < Utc::now().beginning_of_day())
-            .collect::<Vec<&Task>>();
+            .flat_map(|task_id| self.storage.get_dependents(*task_id))
+            .filter(|task| task.blocked)
+            .filter_map(|task| task.id)
+            .collect::<BTreeSet<i32>>();
+
+        self.update_tasks(task_ids, |task| task.completed = completed)?;
+
+        Ok(waiting
+            .into_iter()
+            .filter_map(|task_id| self.storage.get_task(task_id))
+            .filter(|task| !task.blocked)
+            .map(|task| task.title)
+            .collect())
     }
 
-    // Sort the items by the given order
-    pub fn set_sort(&mut self, sorted_by: SortedBy) {
-        if self.sorted_by.is_some() && self.sorted_by.as_ref() == Some(&sorted_by) {
-            self.items.reverse();
+    // Make the selected task wait for the marked ones; Clear the marks
+    pub fn block_selected_by_marked(&mut self) -> Result<usize, String> {
+        let task_id = match self.get_selected().and_then(|task| task.id) {
+            Some(task_id) => task_id,
+            None => return Err("Select the task that has to wait".to_string()),
+        };
+        if self.marked.is_empty() {
+            return Err("Mark the tasks it has to wait for".to_string());
+        }
+
+        let blocker_ids = self.marked.iter().copied().collect::<Vec<i32>>();
+        let added = self.storage.add_dependencies(task_id, &blocker_ids)?;
+        self.clear_marks();
+        self.update_items();
+        Ok(added)
+    }
+
+    // Stop the selected task from waiting for other tasks
+    pub fn unblock_selected(&mut self) -> Result<(), String> {
+        if let Some(task_id) = self.get_selected().and_then(|task| task.id) {
+            self.storage.remove_dependencies(task_id).map_err(write_error)?;
+            self.update_items();
+        }
+        Ok(())
+    }
+
+    // Move the given tasks to the trash, or purge them for good; Update the items
+    pub fn delete_tasks(&mut self, task_ids: &[i32], purge: bool) -> Result<(), String> {
+        if purge {
+            self.storage.purge_tasks(task_ids)
         } else {
-            match &sorted_by {
-                SortedBy::ByName => self.items.sort_by(|a, b| a.title.cmp(&b.title)),
-                SortedBy::ByPriority => self.items.sort_by(|a, b| a.priority.cmp(&b.priority)),
-                _ => self.items.sort_by(|a, b| a.due_date.cmp(&b.due_date)),
-            }
+            self.storage.delete_tasks(task_ids)
+        }
+        .map_err(write_error)?;
+        self.clear_marks();
+        self.update_items();
+        Ok(())
+    }
+
+    // Take the target tasks out of the trash or archive; Update the items
+    pub fn restore_targets(&mut self) -> Result<(), String> {
+        let target_ids = self.target_ids();
+        match self.filter.scope {
+            TaskScope::Trash => self.storage.restore_tasks(&target_ids),
+            TaskScope::Archive => self.storage.unarchive_tasks(&target_ids),
+            TaskScope::Active => Ok(0),
         }
+        .map_err(write_error)?;
+        self.clear_marks();
+        self.update_items();
+        Ok(())
+    }
 
-        self.sorted_by = Some(sorted_by);
+    // Switch to the given part of the archive, or back to the active tasks if already there
+    pub fn toggle_scope(&mut self, scope: TaskScope) {
+        self.filter.scope = if self.filter.scope == scope {
+            TaskScope::Active
+        } else {
+            scope
+        };
+        self.page = 0;
+        self.state.select(None);
+        self.clear_marks();
+        self.update_items();
+    }
+
+    // Archive the tasks completed before the given time; returns their number
+    pub fn archive_completed_before(&mut self, before: DateTime<Utc>) -> Result<usize, String> {
+        let archived = self
+            .storage
+            .archive_completed_before(before)
+            .map_err(write_error)?;
+        self.update_items();
+        Ok(archived)
+    }
+
+    // Refresh after another process changed the database
+    // The selection stays on the same task while it is on the current page
+    // Marks on tasks that left the scope are dropped; The selection is cleared if its task left the page
+    pub fn reload(&mut self) {
+        let selected_id = self.get_selected().and_then(|task| task.id);
+        let scope = self.filter.scope;
+        let storage = self.storage;
+        self.marked.retain(|task_id| {
+            storage
+                .get_task(*task_id)
+                .is_some_and(|task| scope.contains(&task))
+        });
+        self.update_items();
+        if selected_id.is_some() {
+            let position = self.items.iter().position(|task| task.id == selected_id);
+            self.state.select(position);
+        }
+    }
+
+    // Get the selected task
+    pub fn get_selected(&self) -> Option<&Task> {
+        self.state.selected().and_then(|i| self.items.get(i))
+    }
+
+    // Number of all tasks, regardless of the list filter
+    pub fn count_all(&self) -> usize {
+        self.storage.count_tasks(&TaskFilter::default())
+    }
+
+    // Number of the uncompleted tasks
+    pub fn count_uncompleted(&self) -> usize {
+        self.storage.count_tasks(&TaskFilter::uncompleted())
+    }
+
+    // Number of the tasks due next week
+    pub fn count_due_next_week(&self) -> usize {
+        self.storage.count_tasks(&TaskFilter::due_next_week())
+    }
+
+    // Number of the late tasks
+    pub fn count_late(&self) -> usize {
+        self.storage.count_tasks(&TaskFilter::late())
+    }
+
+    // Estimates of the open, due next week (late excluded) and late tasks
+    pub fn sum_estimates(&self) -> [EstimateTotal; 3] {
+        [
+            self.storage.sum_estimates(&TaskFilter::uncompleted()),
+            self.storage.sum_estimates(&TaskFilter::upcoming()),
+            self.storage.sum_estimates(&TaskFilter::late()),
+        ]
+    }
+
+    // Make the given field the primary sort key (or flip its direction) and remember the sort
+    // Leaves the focus ranking
+    pub fn set_sort(&mut self, sorted_by: SortedBy) -> Result<(), String> {
+        self.focus = false;
+        self.sort.promote(sorted_by);
+        self.update_items();
+        self.storage
+            .set_setting(SORT_SETTING, &self.sort.to_string())
+            .map(|_| ())
+            .map_err(write_error)
     }
 }
 
 // Build the UI (list) for task list
-pub fn get_list_items_ui<'a>(tasks: &'a [Task]) -> Vec<ListItem<'a>> {
-    return tasks
+pub fn get_list_items_ui<'a>(
+    tasks: &'a [Task],
+    marked: &BTreeSet<i32>,
+    running_timer: Option<&RunningTimer>,
+    focus_weights: Option<&FocusWeights>,
+    theme: &Theme,
+) -> Vec<ListItem<'a>> {
+    let now = Utc::now();
+    tasks
     .iter()
     .map(|i| {
         let mut lines = Vec::new();
 
-        let title_color = match i.priority {
-            1 => Color::Yellow,
-            2 => Color::Red,
-            _ => Color::White,
-        };
+        let priority_style = theme.priority(i.priority);
 
-        lines.push(Line::from(vec![
+        let is_marked = i.id.is_some_and(|task_id| marked.contains(&task_id));
+        let mut title_line = vec![
+            Span::styled(if is_marked { "* " } else { "  " }, theme.highlight),
             Span::from(if i.completed { "[✓] " } else { "[ ] " }),
-            Span::from(i.title.as_str()).fg(title_color),
-        ]));
+            Span::styled(i.title.as_str(), priority_style),
+        ];
+        if i.priority != Priority::None {
+            title_line.push(Span::styled(format!(" ({})", i.priority.label()), priority_style));
+        }
+        if !i.tags.is_empty() {
+            title_line.push(Span::from(format!(" {}", format_tags(&i.tags))));
+        }
+        if i.blocked && !i.completed {
+            title_line.push(Span::styled(" [blocked]", theme.error));
+        }
+        lines.push(Line::from(title_line));
 
-        lines.push(Line::from(vec![
-            Span::from(format!("    Due: {}", i.due_date.format("%d.%m.%Y"))),
+        let mut details_line = vec![
+            Span::from(format!("      Due: {}", i.due_date.format(DATE_FORMAT))),
             Span::from(format!(" Description: {}", i.description)),
-        ]));
-        ListItem::new(lines).style(Style::default().fg(Color::White))
+        ];
+        if let Some(defer_until) = i.defer_until.filter(|defer_until| *defer_until > now) {
+            details_line.push(Span::from(format!(" Starts: {}", defer_until.format(DATE_FORMAT))));
+        }
+        // The running timer counts in, so the total grows on every tick
+        let running = running_timer.filter(|timer| i.id == Some(timer.task_id));
+        if i.tracked.is_some() || running.is_some() {
+            let mut tracked = i.tracked.unwrap_or_else(chrono::Duration::zero);
+            if let Some(timer) = running {
+                tracked = tracked + (now - timer.started_at);
+            }
+            details_line.push(Span::styled(
+                format!(" Tracked: {}{}", format_tracked(tracked), if running.is_some() { " (running)" } else { "" }),
+                if running.is_some() { theme.highlight } else { theme.text },
+            ));
+        }
+        lines.push(Line::from(details_line));
+
+        if let Some(note) = &i.latest_note {
+            lines.push(Line::from(format!(
+                "      Note {}: {}",
+                note.created_at.format("%d.%m.%Y %H:%M"),
+                note.text
+            )));
+        }
+
+        // Why the task ranks where it does
+        if let Some(focus_weights) = focus_weights {
+            lines.push(Line::from(Span::styled(
+                format!("      {}", score(i, focus_weights, now).describe()),
+                theme.placeholder,
+            )));
+        }
+        ListItem::new(lines).style(theme.text)
     })
-    .collect();
+    .collect()
 }
 
 
-// Build the UI (lines) for statistics infobox
-pub fn get_statistics_ui<'a>(app: &'a App<'a>) -> Vec<Line<'a>> {
-    return vec![
-        Line::from(format!("Total tasks: {}", app.items.items.len())),
+// Build the UI (lines) for the details of the selected task:
+// its fields, metadata, related data and history, the newest change first
+// The lines own their text, so they can be kept until the task changes
+pub fn get_details_ui(app: &App) -> Vec<Line<'static>> {
+    let task = match app.items.get_selected() {
+        Some(task) => task,
+        None => return vec![Line::from("Select a task to see its details")],
+    };
+    let task_id = task.id.unwrap_or(-1);
+
+    let format_time = |time: Option<DateTime<Utc>>| match time {
+        Some(time) => time.format("%d.%m.%Y %H:%M").to_string(),
+        None => "-".to_string(),
+    };
+    let or_dash = |value: String| if value.is_empty() { "-".to_string() } else { value };
+    let status = match (task.completed, task.blocked) {
+        (true, _) => "done",
+        (false, true) => "open, blocked",
+        (false, false) => "open",
+    };
+    let days_until_due = (task.due_date.date_naive() - Utc::now().date_naive()).num_days();
+    let due = match days_until_due {
+        0 => "today".to_string(),
+        days if days < 0 => format!("{} days late", -days),
+        days => format!("in {} days", days),
+    };
+
+    let mut lines = vec![
+        Line::from(Span::styled(task.title.clone(), app.theme.priority(task.priority))),
+        Line::from(format!("Status:    {}", status)),
+        Line::from(format!("Due:       {} ({})", task.due_date.format(DATE_FORMAT), due)),
+        Line::from(format!("Priority:  {}", task.priority.label())),
+        Line::from(format!("Tags:      {}", or_dash(format_tags(&task.tags)))),
+        Line::from(format!("Reminders: {}", or_dash(format_reminders(&task.reminders)))),
+        Line::from(format!(
+            "Estimate:  {}",
+            task.estimate.map(|estimate| estimate.label()).unwrap_or("-".to_string())
+        )),
+        Line::from(format!(
+            "Starts:    {}",
+            task.defer_until
+                .map(|date| date.format(DATE_FORMAT).to_string())
+                .unwrap_or("-".to_string())
+        )),
+        Line::from(format!("Id:        {}, version {}", task_id, task.version)),
+        Line::from(""),
+        Line::from(task.description.clone()),
+        Line::from(""),
+        Line::from(format!("Created:   {}", format_time(task.created_at))),
+        Line::from(format!("Updated:   {}", format_time(task.updated_at))),
+        Line::from(format!("Completed: {}", format_time(task.completed_at))),
         Line::from(format!(
-            "Uncompleted tasks: {}",
-            app.items.get_uncompleted().len()
+            "Tracked:   {}",
+            task.tracked.map(format_tracked).unwrap_or("-".to_string())
         )),
         Line::from(format!(
-            "Due next week: {}",
-            app.items.get_due_next_week().len()
+            "Pomodoros: {}",
+            app.storage.count_task_pomodoros(task_id)
         )),
-        Line::from(format!("Late: {}", app.items.get_late().len())),
     ];
+    let linked_titles = |tasks: Vec<Task>| {
+        tasks
+            .iter()
+            .map(|task| format!("{}{}", task.title, if task.completed { " ✓" } else { "" }))
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+    let blockers = app.storage.get_blockers(task_id);
+    if !blockers.is_empty() {
+        lines.push(Line::from(format!("Waits for: {}", linked_titles(blockers))));
+    }
+    let dependents = app.storage.get_dependents(task_id);
+    if !dependents.is_empty() {
+        lines.push(Line::from(format!("Blocks:    {}", linked_titles(dependents))));
+    }
+    if task.archived_at.is_some() {
+        lines.push(Line::from(format!("Archived:  {}", format_time(task.archived_at))));
+    }
+    if task.deleted_at.is_some() {
+        lines.push(Line::from(format!("Deleted:   {}", format_time(task.deleted_at))));
+    }
+    lines.push(Line::from(""));
+
+    let attachments = app.storage.get_attachments(task_id);
+    if !attachments.is_empty() {
+        lines.push(Line::from("Attachments:"));
+        for (i, attachment) in attachments.iter().enumerate() {
+            lines.push(Line::from(format!("{}. {} ({})", i + 1, attachment.label, attachment.target)));
+        }
+        lines.push(Line::from(""));
+    }
+
+    let notes = app.storage.get_notes(task_id);
+    if !notes.is_empty() {
+        lines.push(Line::from("Notes:"));
+        for note in notes {
+            lines.push(Line::from(format!("{} {}", format_time(Some(note.created_at)), note.text)));
+        }
+        lines.push(Line::from(""));
+    }
+
+    lines.push(Line::from("History:"));
+    for change in app.storage.get_task_history(task_id) {
+        let time = change.changed_at.format("%d.%m.%Y %H:%M");
+        lines.push(Line::from(match (change.old_value, change.new_value) {
+            (Some(old_value), Some(new_value)) => {
+                format!("{} {}: {} → {}", time, change.field, old_value, new_value)
+            }
+            (None, Some(new_value)) => format!("{} {}: {}", time, change.field, new_value),
+            _ => format!("{} {}", time, change.field),
+        }));
+    }
+
+    lines
 }
 
-// Build the UI (lines) for instructions infobox
-pub fn get_instructions_ui<'a>() -> Vec<Line<'a>> {
-    return vec![
-            "Enter - toggle do/done".into(),
-            "a - add a task".into(),
-            "e - edit a task".into(),
-            "x - delete a task".into(),
-            "d - sort by due date".into(),
-            "f - sort by name".into(),
-            "g - sort by priority".into(),
-            "q - quit".into(),
-        ];
-}
\ No newline at end of file
+// Build the UI (lines) for instructions infobox from the active keymap
+pub fn get_instructions_ui<'a>(keymap: &KeyMap) -> Vec<Line<'a>> {
+    Action::ALL
+        .iter()
+        .filter(|action| !keymap.keys_for(**action).is_empty())
+        .map(|action| {
+            Line::from(format!(
+                "{} - {}",
+                keymap.key_labels(*action),
+                action.description()
+            ))
+        })
+        .collect()
+}
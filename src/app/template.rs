@@ -0,0 +1,107 @@
+// Task templates: saved from a task and picked when adding a new one
+use chrono::{DateTime, Utc};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::app::models::{Task, TaskTemplate};
+use crate::app::theme::Theme;
+
+// Template with the fields of the task; its due date is kept relative to today
+// and the tasks it waits for become the subtasks
+pub fn template_from_task(name: &str, task: &Task, blockers: &[Task], now: DateTime<Utc>) -> TaskTemplate {
+    TaskTemplate {
+        id: None,
+        name: name.trim().to_string(),
+        title: task.title.clone(),
+        description: task.description.clone(),
+        priority: task.priority,
+        due_offset_days: (task.due_date.date_naive() - now.date_naive()).num_days().max(0),
+        subtasks: blockers.iter().map(|blocker| blocker.title.clone()).collect(),
+    }
+}
+
+// Choice between a blank task and the saved templates, shown when adding a task
+pub struct TemplatePicker {
+    pub templates: Vec<TaskTemplate>,
+    // 0 is the blank task, the templates follow
+    pub selected: usize,
+}
+
+impl TemplatePicker {
+    pub fn new(templates: Vec<TaskTemplate>) -> TemplatePicker {
+        TemplatePicker {
+            templates,
+            selected: 0,
+        }
+    }
+
+    pub fn next(&mut self) {
+        self.selected = (self.selected + 1).min(self.templates.len());
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    // None when the blank task is selected
+    pub fn selected_template(&self) -> Option<&TaskTemplate> {
+        self.selected.checked_sub(1).and_then(|i| self.templates.get(i))
+    }
+
+    // Forget a template once it was deleted from the storage
+    pub fn remove(&mut self, template_id: i32) {
+        self.templates.retain(|template| template.id != Some(template_id));
+        self.selected = self.selected.min(self.templates.len());
+    }
+}
+
+// For example "Release: Release 1.2 (High), due in 7 days, 3 subtasks"
+fn describe(template: &TaskTemplate) -> String {
+    let mut description = format!(
+        "{}: {} ({}), due in {} days",
+        template.name,
+        template.title,
+        template.priority.label(),
+        template.due_offset_days
+    );
+    if !template.subtasks.is_empty() {
+        description.push_str(&format!(", {} subtasks", template.subtasks.len()));
+    }
+    description
+}
+
+// Draw the choices in a box in the middle of the given area
+pub fn draw_template_picker(f: &mut Frame, area: Rect, picker: &TemplatePicker, theme: &Theme) {
+    let mut choices = vec!["Blank task".to_string()];
+    choices.extend(picker.templates.iter().map(describe));
+
+    let mut lines = choices
+        .into_iter()
+        .enumerate()
+        .map(|(i, choice)| {
+            if i == picker.selected {
+                Line::from(Span::styled(format!(">> {}", choice), theme.highlight))
+            } else {
+                Line::from(format!("   {}", choice))
+            }
+        })
+        .collect::<Vec<Line>>();
+    lines.push(Line::from(""));
+    lines.push(Line::from("Enter - use, x - delete template, Esc - cancel"));
+
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let width = (area.width * 3 / 4).max(30).min(area.width);
+    let picker_area = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    f.render_widget(Clear, picker_area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(Block::new().borders(Borders::ALL).title("New task from"))
+            .style(theme.text),
+        picker_area,
+    );
+}
@@ -0,0 +1,275 @@
+// Color scheme of the user interface
+// Built-in themes can be tweaked from the config file and are adapted to the terminal
+use ratatui::style::{Color, Modifier, Style};
+use std::env;
+use std::str::FromStr;
+
+use crate::app::config::ThemeConfig;
+use crate::app::models::Priority;
+
+pub struct Theme {
+    pub text: Style,
+    pub placeholder: Style,
+    pub highlight: Style,
+    pub cursor: Style,
+    pub error: Style,
+    pub chart: Style,
+    pub priority_low: Style,
+    pub priority_medium: Style,
+    pub priority_high: Style,
+    pub priority_urgent: Style,
+}
+
+// How many colors the terminal is able to display
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorSupport {
+    Ansi16,
+    Indexed256,
+    TrueColor,
+}
+
+impl Theme {
+    pub fn dark() -> Theme {
+        Theme {
+            text: Style::new().fg(Color::White),
+            placeholder: Style::new().fg(Color::DarkGray),
+            highlight: Style::new()
+                .fg(Color::Black)
+                .bg(Color::LightGreen)
+                .add_modifier(Modifier::BOLD),
+            cursor: Style::new().fg(Color::Black).bg(Color::White),
+            error: Style::new().fg(Color::Red),
+            chart: Style::new().fg(Color::LightGreen),
+            priority_low: Style::new().fg(Color::Cyan),
+            priority_medium: Style::new().fg(Color::Yellow),
+            priority_high: Style::new().fg(Color::Red),
+            priority_urgent: Style::new().fg(Color::LightRed).add_modifier(Modifier::BOLD),
+        }
+    }
+
+    pub fn light() -> Theme {
+        Theme {
+            text: Style::new().fg(Color::Black),
+            placeholder: Style::new().fg(Color::DarkGray),
+            highlight: Style::new()
+                .fg(Color::White)
+                .bg(Color::Green)
+                .add_modifier(Modifier::BOLD),
+            cursor: Style::new().fg(Color::White).bg(Color::Black),
+            error: Style::new().fg(Color::Red),
+            chart: Style::new().fg(Color::Green),
+            priority_low: Style::new().fg(Color::Blue),
+            // An ANSI color, a custom orange would turn into the red of high on 16 color terminals
+            priority_medium: Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            priority_high: Style::new().fg(Color::Red),
+            priority_urgent: Style::new().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+        }
+    }
+
+    pub fn high_contrast() -> Theme {
+        Theme {
+            text: Style::new().fg(Color::White),
+            placeholder: Style::new().fg(Color::Gray),
+            highlight: Style::new()
+                .fg(Color::Black)
+                .bg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+            cursor: Style::new().fg(Color::Black).bg(Color::Yellow),
+            error: Style::new().fg(Color::LightRed).add_modifier(Modifier::BOLD),
+            chart: Style::new().fg(Color::LightYellow),
+            priority_low: Style::new().fg(Color::LightCyan),
+            priority_medium: Style::new()
+                .fg(Color::LightYellow)
+                .add_modifier(Modifier::BOLD),
+            priority_high: Style::new().fg(Color::LightRed).add_modifier(Modifier::BOLD),
+            priority_urgent: Style::new()
+                .fg(Color::LightMagenta)
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+        }
+    }
+
+    // Used when NO_COLOR is set: only the terminal's own colors and text modifiers
+    pub fn monochrome() -> Theme {
+        Theme {
+            text: Style::new(),
+            placeholder: Style::new().add_modifier(Modifier::DIM),
+            highlight: Style::new().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+            cursor: Style::new().add_modifier(Modifier::REVERSED),
+            error: Style::new().add_modifier(Modifier::BOLD),
+            chart: Style::new(),
+            priority_low: Style::new().add_modifier(Modifier::ITALIC),
+            priority_medium: Style::new().add_modifier(Modifier::BOLD),
+            priority_high: Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            priority_urgent: Style::new()
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED | Modifier::ITALIC),
+        }
+    }
+
+    // Build the theme selected in the config file, applying overrides and terminal limitations
+    pub fn from_config(config: &ThemeConfig) -> Result<Theme, String> {
+        if no_color_requested() {
+            return Ok(Theme::monochrome());
+        }
+
+        let mut theme = match config.name.as_deref() {
+            None | Some("dark") => Theme::dark(),
+            Some("light") => Theme::light(),
+            Some("high-contrast") => Theme::high_contrast(),
+            Some(other) => return Err(format!("Unknown theme '{}'", other)),
+        };
+
+        for (name, value) in &config.overrides {
+            let color = Color::from_str(value)
+                .map_err(|_| format!("Invalid color '{}' for '{}'", value, name))?;
+            match name.as_str() {
+                "text" => theme.text = theme.text.fg(color),
+                "placeholder" => theme.placeholder = theme.placeholder.fg(color),
+                "highlight" => theme.highlight = theme.highlight.bg(color),
+                "cursor" => theme.cursor = theme.cursor.bg(color),
+                "error" => theme.error = theme.error.fg(color),
+                "chart" => theme.chart = theme.chart.fg(color),
+                "priority_low" => theme.priority_low = theme.priority_low.fg(color),
+                "priority_medium" => theme.priority_medium = theme.priority_medium.fg(color),
+                "priority_high" => theme.priority_high = theme.priority_high.fg(color),
+                "priority_urgent" => theme.priority_urgent = theme.priority_urgent.fg(color),
+                _ => return Err(format!("Unknown theme color '{}'", name)),
+            }
+        }
+
+        let color_support = match config.colors.as_deref() {
+            None | Some("auto") => detect_color_support(),
+            Some("16") => ColorSupport::Ansi16,
+            Some("256") => ColorSupport::Indexed256,
+            Some("truecolor") => ColorSupport::TrueColor,
+            Some(other) => return Err(format!("Unknown color support '{}'", other)),
+        };
+        theme.adapt_to(color_support);
+
+        Ok(theme)
+    }
+
+    // Style of a task title and priority label with the given priority
+    pub fn priority(&self, priority: Priority) -> Style {
+        match priority {
+            Priority::None => self.text,
+            Priority::Low => self.priority_low,
+            Priority::Medium => self.priority_medium,
+            Priority::High => self.priority_high,
+            Priority::Urgent => self.priority_urgent,
+        }
+    }
+
+    // Replace the colors the terminal cannot show with the closest ones it can
+    fn adapt_to(&mut self, color_support: ColorSupport) {
+        for style in [
+            &mut self.text,
+            &mut self.placeholder,
+            &mut self.highlight,
+            &mut self.cursor,
+            &mut self.error,
+            &mut self.chart,
+            &mut self.priority_low,
+            &mut self.priority_medium,
+            &mut self.priority_high,
+            &mut self.priority_urgent,
+        ] {
+            style.fg = style.fg.map(|color| downgrade_color(color, color_support));
+            style.bg = style.bg.map(|color| downgrade_color(color, color_support));
+        }
+    }
+}
+
+// See https://no-color.org/
+fn no_color_requested() -> bool {
+    env::var("NO_COLOR").is_ok_and(|value| !value.is_empty())
+}
+
+fn detect_color_support() -> ColorSupport {
+    let colorterm = env::var("COLORTERM").unwrap_or_default();
+    let term = env::var("TERM").unwrap_or_default();
+    if colorterm.contains("truecolor") || colorterm.contains("24bit") {
+        ColorSupport::TrueColor
+    } else if term.contains("256color") {
+        ColorSupport::Indexed256
+    } else {
+        ColorSupport::Ansi16
+    }
+}
+
+// Standard xterm values of the 16 ANSI colors
+const ANSI_16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+fn downgrade_color(color: Color, color_support: ColorSupport) -> Color {
+    match (color, color_support) {
+        (Color::Rgb(r, g, b), ColorSupport::Ansi16) => nearest_ansi_16(r, g, b),
+        (Color::Indexed(_), ColorSupport::Ansi16) => Color::Reset,
+        (Color::Rgb(r, g, b), ColorSupport::Indexed256) => {
+            // Position in the 6x6x6 color cube of the 256 color palette
+            let level = |c: u8| ((c as u16 * 5 + 127) / 255) as u8;
+            Color::Indexed(16 + 36 * level(r) + 6 * level(g) + level(b))
+        }
+        (color, _) => color,
+    }
+}
+
+fn nearest_ansi_16(r: u8, g: u8, b: u8) -> Color {
+    let distance = |(cr, cg, cb): (u8, u8, u8)| {
+        let dr = r as i32 - cr as i32;
+        let dg = g as i32 - cg as i32;
+        let db = b as i32 - cb as i32;
+        dr * dr + dg * dg + db * db
+    };
+
+    ANSI_16
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::Reset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priorities_stay_distinct_on_16_color_terminals() {
+        let priorities = [Priority::None, Priority::Low, Priority::Medium, Priority::High, Priority::Urgent];
+        for (name, mut theme) in [
+            ("dark", Theme::dark()),
+            ("light", Theme::light()),
+            ("high-contrast", Theme::high_contrast()),
+            ("monochrome", Theme::monochrome()),
+        ] {
+            theme.adapt_to(ColorSupport::Ansi16);
+            for (i, priority) in priorities.iter().enumerate() {
+                for other in &priorities[i + 1..] {
+                    assert_ne!(
+                        theme.priority(*priority),
+                        theme.priority(*other),
+                        "{:?} and {:?} look the same in the {} theme",
+                        priority,
+                        other,
+                        name
+                    );
+                }
+            }
+        }
+    }
+}
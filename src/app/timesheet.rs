@@ -0,0 +1,62 @@
+// Timesheet export: tracked time by day and task as CSV, for billing
+use chrono::{Datelike, NaiveDate, Utc};
+use std::error::Error;
+
+use crate::app::models::{parse_date, TimesheetRow, DATE_FORMAT};
+use crate::app::storage::Storage;
+
+// `timesheet [--since dd.mm.yyyy] [--until dd.mm.yyyy]`, by default the current month until today
+pub fn print_timesheet(storage: &Storage, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let today = Utc::now().date_naive();
+    let since = date_arg(args, "--since")?.unwrap_or(today.with_day(1).unwrap_or(today));
+    let until = date_arg(args, "--until")?.unwrap_or(today);
+
+    print!("{}", to_csv(&storage.get_timesheet(since, until, Utc::now())));
+    Ok(())
+}
+
+// The date following the given option, if the option is there
+fn date_arg(args: &[String], option: &str) -> Result<Option<NaiveDate>, String> {
+    match args.iter().position(|arg| arg == option) {
+        Some(i) => args
+            .get(i + 1)
+            .and_then(|date| parse_date(date))
+            .map(|date| Some(date.date_naive()))
+            .ok_or(format!("{} expects a date in format dd.mm.yyyy", option)),
+        None => Ok(None),
+    }
+}
+
+// One line per day and task in hours, followed by the total of the day
+fn to_csv(rows: &[TimesheetRow]) -> String {
+    let mut csv = "date,task_id,title,hours\n".to_string();
+    let mut total_seconds = 0;
+    for (i, row) in rows.iter().enumerate() {
+        csv.push_str(&format!(
+            "{},{},{},{}\n",
+            row.day.format(DATE_FORMAT),
+            row.task_id,
+            quote(&row.title),
+            hours(row.tracked.num_seconds())
+        ));
+
+        total_seconds += row.tracked.num_seconds();
+        if rows.get(i + 1).is_none_or(|next| next.day != row.day) {
+            csv.push_str(&format!(
+                "{},,Total,{}\n",
+                row.day.format(DATE_FORMAT),
+                hours(total_seconds)
+            ));
+            total_seconds = 0;
+        }
+    }
+    csv
+}
+
+fn hours(seconds: i64) -> String {
+    format!("{:.2}", seconds as f64 / 3600.0)
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
@@ -1,3 +1,4 @@
+use chrono::Utc;
 use crossterm::event::{self, Event, KeyCode, KeyEventKind};
 use ratatui::{prelude::*, widgets::*};
 use std::{
@@ -5,22 +6,115 @@ use std::{
     time::{Duration, Instant},
 };
 
-use crate::app::models::Task;
-use crate::app::storage::Storage;
-use crate::app::{task_edit::*, task_list::*};
+use crate::app::attachments::{open_attachment, opener, parse_attachment};
+use crate::app::config::Config;
+use crate::app::confirm::{draw_confirmation, Confirmation, ConfirmedAction};
+use crate::app::focus::FocusWeights;
+use crate::app::keymap::{Action, KeyMap};
+use crate::app::models::{
+    format_clock, format_tracked, parse_date, parse_snooze, parse_tags, Estimate, Priority, RunningTimer, Task,
+    DATE_FORMAT,
+};
+use crate::app::pomodoro::{draw_pomodoro, PomodoroTimer};
+use crate::app::prompt::{draw_prompt, Prompt, PromptPurpose};
+use crate::app::query::TaskScope;
+use crate::app::quick_add::parse_quick_add;
+use crate::app::reminders::{check_interval, describe, ReminderScheduler};
+use crate::app::sort::{SortSpec, SortedBy};
+use crate::app::storage::{write_error, Storage};
+use crate::app::template::{draw_template_picker, template_from_task, TemplatePicker};
+use crate::app::theme::Theme;
+use crate::app::{statistics::*, task_edit::*, task_list::*};
+
+// Tasks shown on one page of the list unless configured otherwise
+const DEFAULT_PAGE_SIZE: usize = 100;
 
 pub struct App<'a> {
     pub items: crate::app::task_list::TaskList<'a, Task>,
     pub task_edit_dialog_state: TaskEditDialogState,
     pub storage: &'a Storage,
+    pub keymap: KeyMap,
+    pub theme: Theme,
+    // Commands and statistics instead of the details of the selected task
+    pub show_commands: bool,
+    // Lines scrolled off the top of the details, reset when another task is selected
+    details_scroll: u16,
+    // Details of the highlighted task, built again when another task is highlighted or the items are read
+    details: Vec<Line<'static>>,
+    details_task_id: Option<i32>,
+    details_generation: u64,
+    pub prompt: Option<Prompt>,
+    pub confirmation: Option<Confirmation>,
+    pub template_picker: Option<TemplatePicker>,
+    // Result of the last action, shown under the list until the next key press
+    pub message: Option<String>,
+    pub reminder_scheduler: ReminderScheduler,
+    // Reminders fired while the app is open, shown above the list until the next key press
+    pub notifications: Vec<String>,
+    // Time tracking of the task being worked on
+    pub running_timer: Option<RunningTimer>,
+    pub pomodoro: PomodoroTimer,
+    // Estimated work that fits into a week
+    pub weekly_capacity: Option<Estimate>,
+    // Command that opens attachments
+    pub opener: String,
+    // Last seen version of the database, to notice writes of other processes
+    data_version: i64,
 }
 
 impl<'a> App<'a> {
-    pub fn new(storage: &Storage) -> App {
-        App {
-            items: TaskList::with_items_from_storage(&storage),
+    pub fn new(storage: &'a Storage, config: &Config) -> Result<App<'a>, String> {
+        let default_sort = SortSpec::parse(config.list.sort.as_deref().unwrap_or_default())?;
+        Ok(App {
+            items: TaskList::with_items_from_storage(
+                storage,
+                default_sort,
+                config.list.page_size.unwrap_or(DEFAULT_PAGE_SIZE),
+                FocusWeights::from_config(&config.focus),
+            ),
             task_edit_dialog_state: TaskEditDialogState::default(),
-            storage: &storage,
+            storage,
+            keymap: KeyMap::from_config(&config.keys)?,
+            theme: Theme::from_config(&config.theme)?,
+            show_commands: false,
+            details_scroll: 0,
+            details: Vec::new(),
+            details_task_id: None,
+            details_generation: 0,
+            prompt: None,
+            confirmation: None,
+            template_picker: None,
+            message: None,
+            reminder_scheduler: ReminderScheduler::new(check_interval(&config.reminders)),
+            notifications: Vec::new(),
+            running_timer: storage.get_running_timer(),
+            pomodoro: PomodoroTimer::from_config(&config.pomodoro),
+            weekly_capacity: match &config.planning.weekly_capacity {
+                Some(capacity) => Some(Estimate::parse(capacity).ok_or(format!(
+                    "Unknown weekly capacity '{}', expected hours like 40h or points like 20pts",
+                    capacity
+                ))?),
+                None => None,
+            },
+            opener: opener(&config.attachments),
+            data_version: storage.data_version(),
+        })
+    }
+
+    // Periodic work between key presses
+    fn on_tick(&mut self) {
+        if let Some(message) = self.pomodoro.tick(self.storage) {
+            self.message = Some(message);
+            self.items.reload();
+        }
+        let data_version = self.storage.data_version();
+        if data_version != self.data_version {
+            self.data_version = data_version;
+            self.items.reload();
+            self.running_timer = self.storage.get_running_timer();
+        }
+        for reminder in self.reminder_scheduler.poll(self.storage) {
+            self.notifications.push(describe(&reminder));
         }
     }
 }
@@ -30,7 +124,7 @@ pub fn run_app<B: Backend>(
     mut app: App,
     tick_rate: Duration,
 ) -> io::Result<()> {
-    let last_tick = Instant::now();
+    let mut last_tick = Instant::now();
     loop {
         terminal.draw(|f| draw_ui(f, &mut app))?;
         let timeout = tick_rate.saturating_sub(last_tick.elapsed());
@@ -38,14 +132,71 @@ pub fn run_app<B: Backend>(
         if crossterm::event::poll(timeout)? {
             if let Event::Key(key) = event::read()? {
                 if key.kind == KeyEventKind::Press {
-                    if app.task_edit_dialog_state.dialog_active {
+                    app.message = None;
+                    app.notifications.clear();
+                    if let Some(confirmation) = app.confirmation.take() {
+                        // Only an explicit yes runs the action, anything else but n/Esc is ignored
+                        match key.code {
+                            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                                run_confirmed(&mut app, confirmation.action)
+                            }
+                            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {}
+                            _ => app.confirmation = Some(confirmation),
+                        }
+                    } else if let Some(prompt) = app.prompt.as_mut() {
+                        // Handle input for the prompt
+                        match key.code {
+                            KeyCode::Esc => app.prompt = None,
+                            KeyCode::Enter => submit_prompt(&mut app),
+                            KeyCode::Backspace => prompt.delete_char(),
+                            KeyCode::Char(to_insert) => prompt.input(to_insert),
+                            _ => {}
+                        }
+                    } else if let Some(picker) = app.template_picker.as_mut() {
+                        // Choose what the new task starts from
+                        match key.code {
+                            KeyCode::Down => picker.next(),
+                            KeyCode::Up => picker.previous(),
+                            KeyCode::Esc => app.template_picker = None,
+                            KeyCode::Enter => {
+                                match picker.selected_template() {
+                                    Some(template) => app.task_edit_dialog_state.create_from_template(template),
+                                    None => app.task_edit_dialog_state.create_a_new_task(),
+                                }
+                                app.template_picker = None;
+                            }
+                            KeyCode::Char('x') => {
+                                if let Some(template) = picker.selected_template() {
+                                    if let Some(template_id) = template.id {
+                                        app.confirmation = Some(Confirmation::new(
+                                            ConfirmedAction::DeleteTemplate(template_id),
+                                            "Delete the template?",
+                                            vec![template.name.clone()],
+                                        ));
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    } else if app.task_edit_dialog_state.conflict.is_some() {
+                        // The edited task was changed elsewhere, let the user decide which version wins
+                        match key.code {
+                            KeyCode::Char('o') => {
+                                app.task_edit_dialog_state.overwrite_conflict(app.storage);
+                                app.items.update_items();
+                            }
+                            KeyCode::Char('r') => app.task_edit_dialog_state.load_conflict(),
+                            KeyCode::Esc => app.task_edit_dialog_state.conflict = None,
+                            _ => {}
+                        }
+                    } else if app.task_edit_dialog_state.dialog_active {
                         // Handle input for the task edit dialog
                         match key.code {
                             KeyCode::Down => app.task_edit_dialog_state.move_cursor_down(),
                             KeyCode::Up => app.task_edit_dialog_state.move_cursor_up(),
                             KeyCode::Esc => app.task_edit_dialog_state.dialog_active = false,
                             KeyCode::Enter => {
-                                app.task_edit_dialog_state.save_task(&app.storage);
+                                app.task_edit_dialog_state.save_task(app.storage);
                                 app.items.update_items();
                             }
                             KeyCode::Left => app.task_edit_dialog_state.move_cursor_left(),
@@ -56,30 +207,478 @@ pub fn run_app<B: Backend>(
                         }
                     } else {
                         // Handle input for the task list navigation, sorting and state change
-                        match key.code {
-                            KeyCode::Char('q') => return Ok(()),
-                            KeyCode::Char('x') => app.items.delete_selected(),
-                            KeyCode::Left => app.items.unselect(),
-                            KeyCode::Down => app.items.next(),
-                            KeyCode::Up => app.items.previous(),
-                            KeyCode::Char('a') => app.task_edit_dialog_state.create_a_new_task(),
-                            KeyCode::Char('e') => match app.items.get_selected() {
-                                Some(task) => app.task_edit_dialog_state.edit_task(task),
-                                None => {}
-                            },
-                            KeyCode::Char('d') => app.items.set_sort(SortedBy::ByDueDate),
-                            KeyCode::Char('f') => app.items.set_sort(SortedBy::ByName),
-                            KeyCode::Char('g') => app.items.set_sort(SortedBy::ByPriority),
-                            KeyCode::Enter => app.items.toggle_completed(),
-                            _ => {}
+                        match app.keymap.action_for(key.code) {
+                            Some(Action::Quit) => return Ok(()),
+                            Some(Action::Delete) => {
+                                let task_ids = app.items.target_ids();
+                                if !task_ids.is_empty() {
+                                    let titles = app.items.titles(&task_ids);
+                                    let (action, question) = match app.items.filter.scope {
+                                        TaskScope::Trash => (
+                                            ConfirmedAction::PurgeTasks(task_ids),
+                                            format!("Purge {} task(s) permanently?", titles.len()),
+                                        ),
+                                        _ => (
+                                            ConfirmedAction::DeleteTasks(task_ids),
+                                            format!("Move {} task(s) to the trash?", titles.len()),
+                                        ),
+                                    };
+                                    app.confirmation = Some(Confirmation::new(action, &question, titles));
+                                }
+                            }
+                            Some(Action::Unselect) => app.items.unselect(),
+                            Some(Action::Next) => app.items.next(),
+                            Some(Action::Previous) => app.items.previous(),
+                            Some(Action::Add) => {
+                                let templates = app.storage.get_templates();
+                                if templates.is_empty() {
+                                    app.task_edit_dialog_state.create_a_new_task()
+                                } else {
+                                    app.template_picker = Some(TemplatePicker::new(templates))
+                                }
+                            }
+                            Some(Action::Clone) => {
+                                // The copy is edited as a new task and saved as a new row
+                                if let Some(task) = app.items.get_selected() {
+                                    let copy = Task {
+                                        id: None,
+                                        completed: false,
+                                        ..task.clone()
+                                    };
+                                    app.task_edit_dialog_state.edit_task(&copy)
+                                }
+                            }
+                            Some(Action::AddNote) => {
+                                if let Some(task) = app.items.get_selected() {
+                                    app.prompt = Some(
+                                        Prompt::new(PromptPurpose::AddNote, &format!("Note on {}:", task.title), "")
+                                            .for_tasks(task.id.into_iter().collect()),
+                                    )
+                                }
+                            }
+                            Some(Action::Attach) => {
+                                if let Some(task) = app.items.get_selected() {
+                                    app.prompt = Some(
+                                        Prompt::new(
+                                            PromptPurpose::Attach,
+                                            &format!(
+                                                "Attach to {} (path or URL, quoted if it has spaces, then a label):",
+                                                task.title
+                                            ),
+                                            "",
+                                        )
+                                        .for_tasks(task.id.into_iter().collect()),
+                                    )
+                                }
+                            }
+                            Some(Action::OpenAttachment) => {
+                                open_attachment_prompt(&mut app, PromptPurpose::OpenAttachment, "Open")
+                            }
+                            Some(Action::RemoveAttachment) => {
+                                open_attachment_prompt(&mut app, PromptPurpose::RemoveAttachment, "Remove")
+                            }
+                            Some(Action::SaveTemplate) => {
+                                if let Some(task) = app.items.get_selected() {
+                                    app.prompt = Some(
+                                        Prompt::new(PromptPurpose::SaveTemplate, "Save as template named:", &task.title)
+                                            .for_tasks(task.id.into_iter().collect()),
+                                    )
+                                }
+                            }
+                            Some(Action::QuickAdd) => {
+                                app.prompt = Some(Prompt::new(
+                                    PromptPurpose::QuickAdd,
+                                    "Quick add (Buy milk tomorrow !2 #home @errands):",
+                                    "",
+                                ))
+                            }
+                            Some(Action::Edit) => {
+                                if let Some(task) = app.items.get_selected() {
+                                    app.task_edit_dialog_state.edit_task(task)
+                                }
+                            }
+                            Some(Action::ToggleFocus) => app.items.toggle_focus(),
+                            Some(Action::SortByDueDate) => app.message = app.items.set_sort(SortedBy::DueDate).err(),
+                            Some(Action::SortByName) => app.message = app.items.set_sort(SortedBy::Name).err(),
+                            Some(Action::SortByPriority) => app.message = app.items.set_sort(SortedBy::Priority).err(),
+                            Some(Action::ToggleCompleted) => {
+                                let task_ids = app.items.target_ids();
+                                if !task_ids.is_empty() {
+                                    let completed = !app.items.all_completed(&task_ids);
+                                    let question = format!(
+                                        "{} {} tasks?",
+                                        if completed { "Complete" } else { "Reopen" },
+                                        task_ids.len()
+                                    );
+                                    let action = ConfirmedAction::SetCompleted { task_ids, completed };
+                                    confirm_bulk(&mut app, action, &question);
+                                }
+                            }
+                            Some(Action::ToggleHideCompleted) => app.items.toggle_hide_completed(),
+                            Some(Action::ToggleDetails) => app.show_commands = !app.show_commands,
+                            Some(Action::ScrollDetailsDown) => {
+                                app.details_scroll = app.details_scroll.saturating_add(1)
+                            }
+                            Some(Action::ScrollDetailsUp) => {
+                                app.details_scroll = app.details_scroll.saturating_sub(1)
+                            }
+                            Some(Action::ShowTrash) => app.items.toggle_scope(TaskScope::Trash),
+                            Some(Action::ShowArchive) => app.items.toggle_scope(TaskScope::Archive),
+                            Some(Action::ArchiveCompleted) => {
+                                app.prompt = Some(Prompt::new(
+                                    PromptPurpose::ArchiveCompletedBefore,
+                                    "Archive tasks completed before (dd.mm.yyyy):",
+                                    &Utc::now().format(DATE_FORMAT).to_string(),
+                                ))
+                            }
+                            Some(Action::Restore) => app.message = app.items.restore_targets().err(),
+                            Some(Action::Mark) => app.items.toggle_mark(),
+                            Some(Action::MarkRange) => app.items.mark_range(),
+                            Some(Action::ClearMarks) => app.items.clear_marks(),
+                            Some(Action::SetPriority) => open_bulk_prompt(
+                                &mut app,
+                                PromptPurpose::SetPriority,
+                                "Priority (none/low/medium/high/urgent or 0-4):",
+                            ),
+                            Some(Action::Reschedule) => open_bulk_prompt(
+                                &mut app,
+                                PromptPurpose::Reschedule,
+                                "Due date (dd.mm.yyyy):",
+                            ),
+                            Some(Action::Tag) => open_bulk_prompt(
+                                &mut app,
+                                PromptPurpose::Tag,
+                                "Tags to add (-tag to remove):",
+                            ),
+                            Some(Action::ToggleTimer) => toggle_timer(&mut app),
+                            Some(Action::BlockedBy) => {
+                                app.message = Some(match app.items.block_selected_by_marked() {
+                                    Ok(added) => format!("Added {} dependencies", added),
+                                    Err(e) => e,
+                                })
+                            }
+                            Some(Action::Unblock) => app.message = app.items.unblock_selected().err(),
+                            Some(Action::ToggleActionable) => app.items.toggle_actionable(),
+                            Some(Action::TogglePomodoro) => {
+                                if app.pomodoro.current.is_some() {
+                                    app.pomodoro.stop();
+                                } else if let Some(task) = app.items.get_selected() {
+                                    if let Some(task_id) = task.id {
+                                        app.pomodoro.start(task_id, &task.title);
+                                    }
+                                }
+                            }
+                            Some(Action::Snooze) => open_bulk_prompt(
+                                &mut app,
+                                PromptPurpose::Snooze,
+                                "Snooze until (1 - tomorrow, 2 - next week, 0 - show now, or dd.mm.yyyy):",
+                            ),
+                            Some(Action::ToggleDeferred) => app.items.toggle_deferred(),
+                            Some(Action::NextPage) => app.items.next_page(),
+                            Some(Action::PreviousPage) => app.items.previous_page(),
+                            None => {}
                         }
                     }
                 }
             }
         }
+        if last_tick.elapsed() >= tick_rate {
+            app.on_tick();
+            last_tick = Instant::now();
+        }
+    }
+}
+
+// Run the action the user has agreed to
+fn run_confirmed(app: &mut App, action: ConfirmedAction) {
+    match action {
+        ConfirmedAction::DeleteTasks(task_ids) => {
+            app.message = app.items.delete_tasks(&task_ids, false).err()
+        }
+        ConfirmedAction::PurgeTasks(task_ids) => app.message = app.items.delete_tasks(&task_ids, true).err(),
+        ConfirmedAction::SetPriority { task_ids, priority } => {
+            app.message = app.items.update_tasks(&task_ids, |task| task.priority = priority).err()
+        }
+        ConfirmedAction::Reschedule { task_ids, due_date } => {
+            app.message = app.items.update_tasks(&task_ids, |task| task.due_date = due_date).err()
+        }
+        ConfirmedAction::Tag { task_ids, added, removed } => {
+            let tagged = app.items.update_tasks(&task_ids, |task| {
+                task.tags.retain(|tag| !removed.contains(tag));
+                task.tags.extend(added.iter().cloned());
+                task.tags = parse_tags(&task.tags.join(" "));
+            });
+            app.message = tagged.err()
+        }
+        ConfirmedAction::RemoveAttachment { task_id, attachment } => {
+            app.message = Some(match app.storage.remove_attachment(task_id, &attachment) {
+                Ok(()) => format!("Removed {}", attachment.label),
+                Err(error) => write_error(error),
+            });
+            app.items.reload();
+        }
+        ConfirmedAction::DeleteTemplate(template_id) => match app.storage.delete_template(template_id) {
+            Ok(()) => {
+                if let Some(picker) = app.template_picker.as_mut() {
+                    picker.remove(template_id);
+                }
+            }
+            Err(error) => app.message = Some(write_error(error)),
+        },
+        ConfirmedAction::SetCompleted { task_ids, completed } => {
+            app.message = match app.items.set_completed(&task_ids, completed) {
+                Ok(unblocked) if !unblocked.is_empty() => Some(format!("Unblocked: {}", unblocked.join(", "))),
+                Ok(_) => None,
+                Err(error) => Some(error),
+            }
+        }
+    }
+}
+
+// Ask before a change to several tasks; a change to a single task is run right away
+fn confirm_bulk(app: &mut App, action: ConfirmedAction, question: &str) {
+    if action.task_ids().len() > 1 {
+        let titles = app.items.titles(action.task_ids());
+        app.confirmation = Some(Confirmation::new(action, question, titles));
+    } else {
+        run_confirmed(app, action);
+    }
+}
+
+// Start the timer on the selected task, or stop it when it runs there or nothing is selected
+// Starting a timer stops the one running on another task
+fn toggle_timer(app: &mut App) {
+    let now = Utc::now();
+    let selected_id = app.items.get_selected().and_then(|task| task.id);
+    let running_timer = app.running_timer.take();
+    match (selected_id, running_timer) {
+        (Some(task_id), running_timer)
+            if running_timer.as_ref().is_none_or(|timer| timer.task_id != task_id) =>
+        {
+            app.message = Some(match app.storage.start_timer(task_id, now) {
+                Ok(()) => "Timer started".to_string(),
+                Err(error) => write_error(error),
+            });
+        }
+        (_, Some(timer)) => {
+            app.message = Some(match app.storage.stop_timer(now) {
+                Ok(_) => format!(
+                    "Tracked {} on {}",
+                    format_tracked(now - timer.started_at),
+                    timer.title
+                ),
+                Err(error) => write_error(error),
+            });
+        }
+        _ => {}
+    }
+    app.running_timer = app.storage.get_running_timer();
+    app.items.update_items();
+}
+
+// Ask for the value of a bulk operation, if there are tasks to apply it to
+fn open_bulk_prompt(app: &mut App, purpose: PromptPurpose, label: &str) {
+    let task_ids = app.items.target_ids();
+    if !task_ids.is_empty() {
+        app.prompt = Some(
+            Prompt::new(purpose, &format!("[{} task(s)] {}", task_ids.len(), label), "").for_tasks(task_ids),
+        );
+    }
+}
+
+// Ask for the number of one of the attachments of the selected task
+fn open_attachment_prompt(app: &mut App, purpose: PromptPurpose, verb: &str) {
+    if let Some(task_id) = app.items.get_selected().and_then(|task| task.id) {
+        let count = app.storage.get_attachments(task_id).len();
+        if count > 0 {
+            app.prompt = Some(
+                Prompt::new(purpose, &format!("{} attachment 1-{}:", verb, count), "1").for_tasks(vec![task_id]),
+            )
+        } else {
+            app.message = Some("The task has no attachments".to_string())
+        }
+    }
+}
+
+// Use the text entered in the prompt; the prompt stays open if the text is invalid
+fn submit_prompt(app: &mut App) {
+    let prompt = match app.prompt.as_mut() {
+        Some(prompt) => prompt,
+        None => return,
+    };
+
+    match prompt.purpose {
+        PromptPurpose::ArchiveCompletedBefore => match parse_date(&prompt.input) {
+            Some(before) => match app.items.archive_completed_before(before) {
+                Ok(archived) => {
+                    app.message = Some(format!("Archived {} tasks", archived));
+                    app.prompt = None;
+                }
+                Err(error) => prompt.error = Some(error),
+            },
+            None => prompt.error = Some("Date should be in format dd.mm.yyyy".to_string()),
+        },
+        PromptPurpose::SetPriority => match Priority::parse(&prompt.input) {
+            Some(priority) => {
+                let question = format!("Set priority {} on {} tasks?", priority.label(), prompt.task_ids.len());
+                let task_ids = std::mem::take(&mut prompt.task_ids);
+                app.prompt = None;
+                confirm_bulk(app, ConfirmedAction::SetPriority { task_ids, priority }, &question);
+            }
+            None => prompt.error = Some("Unknown priority".to_string()),
+        },
+        PromptPurpose::Reschedule => match parse_date(&prompt.input) {
+            Some(due_date) => {
+                let question = format!(
+                    "Reschedule {} tasks to {}?",
+                    prompt.task_ids.len(),
+                    due_date.format(DATE_FORMAT)
+                );
+                let task_ids = std::mem::take(&mut prompt.task_ids);
+                app.prompt = None;
+                confirm_bulk(app, ConfirmedAction::Reschedule { task_ids, due_date }, &question);
+            }
+            None => prompt.error = Some("Date should be in format dd.mm.yyyy".to_string()),
+        },
+        PromptPurpose::Snooze => match parse_snooze(&prompt.input, Utc::now()) {
+            Some(defer_until) => match app.items.update_tasks(&prompt.task_ids, |task| task.defer_until = defer_until) {
+                Ok(()) => app.prompt = None,
+                Err(error) => prompt.error = Some(error),
+            },
+            None => prompt.error = Some("Enter 1, 2, 0 or a date in format dd.mm.yyyy".to_string()),
+        },
+        PromptPurpose::QuickAdd => match parse_quick_add(&prompt.input, Utc::now()) {
+            Ok(task) => match app.storage.insert_task(&task) {
+                Ok(_) => {
+                    app.items.update_items();
+                    app.prompt = None;
+                }
+                Err(error) => prompt.error = Some(write_error(error)),
+            },
+            Err(error) => prompt.error = Some(error),
+        },
+        PromptPurpose::SaveTemplate => {
+            let name = prompt.input.trim().to_string();
+            if name.is_empty() {
+                prompt.error = Some("The template needs a name".to_string());
+                return;
+            }
+            if let Some(task) = prompt.task_id().and_then(|task_id| app.storage.get_task(task_id)) {
+                let blockers = task.id.map(|id| app.storage.get_blockers(id)).unwrap_or_default();
+                let template = template_from_task(&name, &task, &blockers, Utc::now());
+                if let Err(error) = app.storage.save_template(&template) {
+                    prompt.error = Some(write_error(error));
+                    return;
+                }
+                app.message = Some(format!("Saved template {}", name));
+            }
+            app.prompt = None;
+        }
+        PromptPurpose::AddNote => {
+            let text = prompt.input.trim().to_string();
+            if text.is_empty() {
+                prompt.error = Some("The note is empty".to_string());
+                return;
+            }
+            if let Some(task_id) = prompt.task_id() {
+                if let Err(error) = app.storage.add_note(task_id, &text, Utc::now()) {
+                    prompt.error = Some(write_error(error));
+                    return;
+                }
+                app.items.reload();
+            }
+            app.prompt = None;
+        }
+        PromptPurpose::Attach => match parse_attachment(&prompt.input) {
+            Ok(attachment) => {
+                if let Some(task_id) = prompt.task_id() {
+                    if let Err(error) = app.storage.add_attachment(task_id, &attachment) {
+                        prompt.error = Some(write_error(error));
+                        return;
+                    }
+                    app.message = Some(format!("Attached {}", attachment.label));
+                    app.items.reload();
+                }
+                app.prompt = None;
+            }
+            Err(error) => prompt.error = Some(error),
+        },
+        PromptPurpose::OpenAttachment | PromptPurpose::RemoveAttachment => {
+            let task_id = prompt.task_id().unwrap_or(-1);
+            let mut attachments = app.storage.get_attachments(task_id);
+            let index = prompt.input.trim().parse::<usize>().ok().and_then(|number| number.checked_sub(1));
+            let attachment = match index.filter(|i| *i < attachments.len()) {
+                Some(i) => attachments.swap_remove(i),
+                None => {
+                    prompt.error = Some(format!("Enter a number from 1 to {}", attachments.len()));
+                    return;
+                }
+            };
+            if prompt.purpose == PromptPurpose::RemoveAttachment {
+                let title = app.storage.get_task(task_id).map(|task| task.title).unwrap_or_default();
+                let question = format!("Remove the attachment from {}?", title);
+                let subjects = vec![attachment.label.clone()];
+                let action = ConfirmedAction::RemoveAttachment { task_id, attachment };
+                app.confirmation = Some(Confirmation::new(action, &question, subjects));
+            } else {
+                app.message = Some(match open_attachment(&app.opener, &attachment) {
+                    Ok(()) => format!("Opened {}", attachment.label),
+                    Err(error) => error,
+                });
+            }
+            app.prompt = None;
+        }
+        PromptPurpose::Tag => {
+            let (removed, added): (Vec<&str>, Vec<&str>) = prompt
+                .input
+                .split_whitespace()
+                .partition(|tag| tag.starts_with('-'));
+            let added = parse_tags(&added.join(" "));
+            let removed = parse_tags(
+                &removed
+                    .iter()
+                    .map(|tag| tag.trim_start_matches('-'))
+                    .collect::<Vec<&str>>()
+                    .join(" "),
+            );
+            let question = format!("Change the tags of {} tasks?", prompt.task_ids.len());
+            let task_ids = std::mem::take(&mut prompt.task_ids);
+            app.prompt = None;
+            confirm_bulk(app, ConfirmedAction::Tag { task_ids, added, removed }, &question);
+        }
     }
 }
 
+// Rows the lines take when word wrapped to the width, as the Paragraph wraps them
+// A word longer than the width is broken over several rows
+fn wrapped_rows(lines: &[Line], width: u16) -> u16 {
+    let width = width.max(1) as usize;
+    let mut rows = 0;
+    for line in lines {
+        let text = line
+            .spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect::<String>();
+        let mut row_width = 0;
+        rows += 1;
+        for word in text.split(' ') {
+            let word_width = Span::raw(word).width();
+            if row_width > 0 && row_width + word_width > width {
+                rows += 1;
+                row_width = 0;
+            }
+            if word_width > 0 {
+                rows += (word_width - 1) / width;
+                row_width += (word_width - 1) % width + 1;
+            }
+            // The space after the word
+            row_width += 1;
+        }
+    }
+    rows.min(u16::MAX as usize) as u16
+}
+
 // Draws the whole user interface
 fn draw_ui(f: &mut Frame, app: &mut App) {
     // Create two chunks of screen in 60-40 ratio
@@ -89,42 +688,177 @@ fn draw_ui(f: &mut Frame, app: &mut App) {
         .split(f.size());
 
     // DRAW LEFT PART
+    // Fired reminders take the top of the left part
+    let list_area = if app.notifications.is_empty() {
+        chunks[0]
+    } else {
+        let left_side = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length((app.notifications.len() as u16 + 2).min(chunks[0].height / 2)),
+                Constraint::Min(0),
+            ])
+            .split(chunks[0]);
+        let notifications = app
+            .notifications
+            .iter()
+            .map(|notification| Line::from(notification.as_str()))
+            .collect::<Vec<Line>>();
+        f.render_widget(
+            Paragraph::new(notifications)
+                .block(Block::new().title("Reminders").borders(Borders::ALL))
+                .style(app.theme.error),
+            left_side[0],
+        );
+        left_side[1]
+    };
+
     // Create a List from all tasks and highlight the currently selected one
-    let task_list = List::new(get_list_items_ui(app.items.items.as_slice()))
-        .block(Block::default().borders(Borders::ALL).title("List"))
-        .highlight_style(
-            Style::default()
-                .bg(Color::LightGreen)
-                .add_modifier(Modifier::BOLD),
-        )
+    let mut list_details = Vec::new();
+    if app.items.focus {
+        list_details.push("ranked by score".to_string());
+    } else if !app.items.sort.keys.is_empty() {
+        list_details.push(format!("sorted by {}", app.items.sort.summary()));
+    }
+    if app.items.filter.completed == Some(false) {
+        list_details.push("completed hidden".to_string());
+    }
+    if app.items.filter.include_deferred {
+        list_details.push("deferred shown".to_string());
+    }
+    if app.items.filter.actionable {
+        list_details.push("actionable only".to_string());
+    }
+    if !app.items.marked.is_empty() {
+        list_details.push(format!("{} marked", app.items.marked.len()));
+    }
+    if app.items.page_count() > 1 {
+        list_details.push(format!("page {}/{}", app.items.page + 1, app.items.page_count()));
+    }
+    let list_name = match app.items.filter.scope {
+        TaskScope::Active if app.items.focus => "Today",
+        TaskScope::Active => "List",
+        TaskScope::Trash => "Trash",
+        TaskScope::Archive => "Archive",
+    };
+    let list_title = if list_details.is_empty() {
+        list_name.to_string()
+    } else {
+        format!("{} ({})", list_name, list_details.join(", "))
+    };
+    let mut list_block = Block::default().borders(Borders::ALL).title(list_title);
+    if let Some(timer) = &app.running_timer {
+        list_block = list_block.title(
+            block::Title::from(Span::styled(
+                format!(" {} {} ", timer.title, format_clock(Utc::now() - timer.started_at)),
+                app.theme.highlight,
+            ))
+            .alignment(Alignment::Right),
+        );
+    }
+    if let Some(message) = &app.message {
+        list_block = list_block.title(
+            block::Title::from(message.as_str()).position(block::Position::Bottom),
+        );
+    }
+    let task_list = List::new(get_list_items_ui(
+            app.items.items.as_slice(),
+            &app.items.marked,
+            app.running_timer.as_ref(),
+            app.items.focus.then_some(&app.items.focus_weights),
+            &app.theme,
+        ))
+        .block(list_block)
+        .highlight_style(app.theme.highlight)
         .highlight_symbol(">> ");
 
-    f.render_stateful_widget(task_list, chunks[0], &mut app.items.state);
+    f.render_stateful_widget(task_list, list_area, &mut app.items.state);
+    if let Some(prompt) = &app.prompt {
+        draw_prompt(f, list_area, prompt, &app.theme);
+    }
+    if let Some(picker) = &app.template_picker {
+        draw_template_picker(f, list_area, picker, &app.theme);
+    }
+    if let Some(confirmation) = &app.confirmation {
+        draw_confirmation(f, f.size(), confirmation, &app.theme);
+    }
 
     // DRAW RIGHT PART
+    // The running pomodoro takes the top of the right part
+    let right_area = if app.pomodoro.current.is_some() {
+        let right_side = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)])
+            .split(chunks[1]);
+        draw_pomodoro(f, right_side[0], &app.pomodoro, &app.theme);
+        right_side[1]
+    } else {
+        chunks[1]
+    };
+
     if app.task_edit_dialog_state.dialog_active {
         let create_or_edit_task = Paragraph::new(get_task_edit_ui(app))
             .block(Block::new().title("Add/Edit Task").borders(Borders::ALL))
-            .style(Style::new().white());
+            .style(app.theme.text)
+            .wrap(Wrap { trim: false });
 
-        f.render_widget(create_or_edit_task, chunks[1]);
-        
+        f.render_widget(create_or_edit_task, right_area);
     } else {
-        // If not editing, display statistics and instructions in vertically split layout
+        // The details of the highlighted task, or the commands, above the statistics
         let right_side = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-            .split(chunks[1]);
+            .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
+            .split(right_area);
+
+        if app.items.get_selected().is_some() && !app.show_commands {
+            let selected_id = app.items.get_selected().and_then(|task| task.id);
+            if selected_id != app.details_task_id || app.items.generation != app.details_generation {
+                if selected_id != app.details_task_id {
+                    app.details_scroll = 0;
+                }
+                app.details = get_details_ui(app);
+                app.details_task_id = selected_id;
+                app.details_generation = app.items.generation;
+            }
+            // Scrolling stops when the last row reaches the top of the pane
+            let rows = wrapped_rows(&app.details, right_side[0].width.saturating_sub(2));
+            let scroll = app.details_scroll.min(rows.saturating_sub(1));
 
-        let instructions = Paragraph::new(get_instructions_ui())
-            .block(Block::new().title("Commands").borders(Borders::ALL))
-            .style(Style::new().white());
+            let details = Paragraph::new(app.details.clone())
+                .block(Block::new().title("Details").borders(Borders::ALL))
+                .style(app.theme.text)
+                .wrap(Wrap { trim: false })
+                .scroll((scroll, 0));
+            f.render_widget(details, right_side[0]);
+            app.details_scroll = scroll;
+        } else {
+            let instructions = Paragraph::new(get_instructions_ui(&app.keymap))
+                .block(Block::new().title("Commands").borders(Borders::ALL))
+                .style(app.theme.text);
+            f.render_widget(instructions, right_side[0]);
+        }
+        draw_statistics(f, right_side[1], app);
+    }
+}
 
-        let statistics = Paragraph::new(get_statistics_ui(app))
-            .block(Block::new().title("Statistics").borders(Borders::ALL))
-            .style(Style::new().white());
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        f.render_widget(instructions, right_side[0]);
-        f.render_widget(statistics, right_side[1]);
+    #[test]
+    fn wrapped_rows_count_the_rows_of_long_lines() {
+        let lines = vec![
+            Line::from("History:"),
+            Line::from(""),
+            Line::from("one two three four"),
+            Line::from("abcdefghijklmnopqrstuvwxy"),
+        ];
+        assert_eq!(wrapped_rows(&lines[..2], 10), 2);
+        // "one two" and "three four"
+        assert_eq!(wrapped_rows(&lines[2..3], 10), 2);
+        // A word over two and a half rows
+        assert_eq!(wrapped_rows(&lines[3..], 10), 3);
+        assert_eq!(wrapped_rows(&lines, 10), 7);
+        assert_eq!(wrapped_rows(&lines, 100), 4);
     }
 }
@@ -5,7 +5,7 @@ use crossterm::{
 };
 use ratatui::prelude::*;
 use rusqlite::{Connection, Result};
-use std::{error::Error, io, time::Duration};
+use std::{error::Error, io, path::Path, time::Duration};
 
 mod app;
 
@@ -13,21 +13,37 @@ mod app;
 // This and the rest of code heavily based on:
 // https://github.com/ratatui-org/ratatui/blob/main/examples/list.rs
 pub fn main() -> Result<(), Box<dyn Error>> {
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    // Load the user configuration before touching the terminal, so errors are readable
+    let config = app::config::Config::load(Path::new("config.toml"))?;
 
     // Initialize connection to the database
     let storage = app::storage::Storage {
         db_con: Connection::open("database.db").expect("Failed to open the DB file"),
     };
+    storage.enable_sharing();
     storage.create_table_if_not_exists();
 
-    // Create an app with 250 ms tick
+    // `remind [--once]` delivers due reminders and `timesheet` exports tracked time, without the UI
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("remind") => {
+            let once = args.iter().any(|arg| arg == "--once");
+            return app::reminders::run_daemon(&storage, &config.reminders, once);
+        }
+        Some("timesheet") => return app::timesheet::print_timesheet(&storage, &args[1..]),
+        _ => {}
+    }
+
+    let app = app::ui::App::new(&storage, &config)?;
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    // Run the app with 250 ms tick
     let tick_rate = Duration::from_millis(250);
-    let app = app::ui::App::new(&storage);
     let res = app::ui::run_app(&mut terminal, app, tick_rate);
 
     // Restore previous terminal state after exit